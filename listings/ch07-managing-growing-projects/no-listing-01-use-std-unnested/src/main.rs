@@ -21,6 +21,8 @@ fn main() {
         .read_line(&mut guess)
         .expect("讀取行數失敗");
 
+    let guess: u32 = guess.trim().parse().expect("請輸入一個數字！");
+
     println!("你的猜測數字：{guess}");
 
     match guess.cmp(&secret_number) {
@@ -9,7 +9,7 @@ use std::fs;
 pub struct Config {
     pub query: String,
     pub file_path: String,
-    pub case_sensitive: bool,
+    pub ignore_case: bool,
 }
 
 // ANCHOR: here
@@ -1,14 +1,12 @@
 // ANCHOR: here
-use std::fs;
-// --省略--
-
-// ANCHOR_END: here
 use std::{
     fs,
     io::{prelude::*, BufReader},
     net::{TcpListener, TcpStream},
 };
+// --省略--
 
+// ANCHOR_END: here
 fn main() {
     let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
 
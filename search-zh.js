@@ -0,0 +1,123 @@
+// Splits search queries the way `search_index` splits the Chinese of the
+// book, and adds the Chinese terms of the English terms in the glossary.
+// Indexes `search_index` didn't rebuild have no glossary and are left alone.
+(function () {
+  if (typeof elasticlunr === 'undefined') {
+    return
+  }
+
+  var CJK = /[\u{3400}-\u{4DBF}\u{4E00}-\u{9FFF}\u{F900}-\u{FAFF}\u{20000}-\u{2FA1F}]/u
+  var WORD = /[\p{L}\p{N}_]/u
+
+  var tokenizer = elasticlunr.tokenizer
+  var load = elasticlunr.Index.load
+  var words = new Set()
+  var longest = 0
+  var englishTerms = []
+
+  elasticlunr.Index.load = function (data) {
+    var index = load.call(this, data)
+    if (!data.glossary) {
+      return index
+    }
+
+    for (var word of data.glossary.words) {
+      words.add(word)
+      longest = Math.max(longest, Array.from(word).length)
+    }
+    for (var english in data.glossary.english) {
+      englishTerms.push({
+        stems: stems(tokenizer(english)),
+        terms: data.glossary.english[english]
+      })
+    }
+
+    // The trimmer of elasticlunr takes everything that isn't ASCII off
+    if (index.pipeline.get().indexOf(elasticlunr.trimmer) >= 0) {
+      index.pipeline.after(elasticlunr.trimmer, trimmer)
+      index.pipeline.remove(elasticlunr.trimmer)
+    }
+    elasticlunr.tokenizer = tokenize
+    return index
+  }
+
+  function trimmer(token) {
+    return token.replace(/^[^\p{L}\p{N}_]+|[^\p{L}\p{N}_]+$/gu, '')
+  }
+  elasticlunr.Pipeline.registerFunction(trimmer, 'trimmer-zh')
+
+  function tokenize(query) {
+    var tokens = []
+    var pieces = tokenizer(query)
+    for (var piece of pieces) {
+      tokens.push(...segment(piece))
+    }
+    for (var term of translations(stems(pieces))) {
+      tokens.push(...segment(term))
+    }
+    return tokens
+  }
+  tokenize.seperator = tokenizer.seperator
+
+  // Chinese goes by the longest word first, like in `cjk_tokens`
+  function segment(piece) {
+    if (!CJK.test(piece)) {
+      return [piece]
+    }
+
+    var tokens = []
+    var chars = Array.from(piece.toLowerCase())
+    var latin = ''
+    var index = 0
+    while (index < chars.length) {
+      var c = chars[index]
+      if (WORD.test(c) && !CJK.test(c)) {
+        latin += c
+        index += 1
+        continue
+      }
+      if (latin) {
+        tokens.push(latin)
+        latin = ''
+      }
+      if (CJK.test(c)) {
+        var length = wordAt(chars, index)
+        tokens.push(chars.slice(index, index + length).join(''))
+        index += length
+      } else {
+        index += 1
+      }
+    }
+    if (latin) {
+      tokens.push(latin)
+    }
+    return tokens
+  }
+
+  function wordAt(chars, index) {
+    for (var length = Math.min(longest, chars.length - index); length > 1; length--) {
+      if (words.has(chars.slice(index, index + length).join(''))) {
+        return length
+      }
+    }
+    return 1
+  }
+
+  function stems(tokens) {
+    return tokens.map(token => elasticlunr.stemmer(trimmer(token)))
+  }
+
+  // The Chinese terms of the English terms whose words the query has in a row
+  function translations(queryStems) {
+    var terms = []
+    for (var english of englishTerms) {
+      var found = english.stems.length > 0 && queryStems.some((_, start) =>
+        english.stems.every((stem, i) => queryStems[start + i] === stem)
+      )
+      if (found) {
+        terms.push(...english.terms)
+      }
+    }
+    return terms
+  }
+})()
@@ -0,0 +1,26 @@
+#!/bin/bash
+
+set -eu
+
+cargo build --release
+
+mkdir -p tmp
+rm -rf tmp/epub
+rm -rf tmp/markdown
+
+# Render the book as Markdown to include all the code listings
+MDBOOK_OUTPUT__MARKDOWN=1 mdbook build -d tmp
+
+# Clean up the Markdown like `nostarch.sh` does, but keep the code block info
+# strings for Ferris and the filename and caption spans for the stylesheet, so
+# skip `remove_markup`.
+mkdir -p tmp/epub
+find tmp/markdown -name "*.md" -print0 | \
+xargs -0 basename | \
+while IFS= read -r filename; do
+  < "tmp/markdown/$filename" ./target/release/remove_links \
+    | ./target/release/link2print \
+    | ./target/release/remove_hidden_lines > "tmp/epub/$filename"
+done
+
+./target/release/epub tmp/epub tmp/book-tw.epub
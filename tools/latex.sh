@@ -0,0 +1,26 @@
+#!/bin/bash
+
+set -eu
+
+cargo build --release
+
+mkdir -p tmp
+rm -rf tmp/latex-markdown
+rm -rf tmp/markdown
+
+# Render the book as Markdown to include all the code listings
+MDBOOK_OUTPUT__MARKDOWN=1 mdbook build -d tmp
+
+# Remove the links followed by `<!-- ignore -->` and the hidden lines. Unlike
+# `nostarch.sh`, keep the other links for footnotes, and the code block info
+# strings and the filename and caption spans for the listings.
+mkdir -p tmp/latex-markdown
+find tmp/markdown -name "*.md" -print0 | \
+xargs -0 basename | \
+while IFS= read -r filename; do
+  < "tmp/markdown/$filename" ./target/release/remove_links \
+    | ./target/release/remove_hidden_lines > "tmp/latex-markdown/$filename"
+done
+
+./target/release/latex tmp/latex-markdown tmp/latex
+echo "Convert the SVG images in tmp/latex/img to PDF, then run \`xelatex book.tex\` twice in tmp/latex."
@@ -0,0 +1,237 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::{fs, path};
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let src_dir = path::Path::new(&args.arg_src_dir);
+    let mut chapters = fs::read_dir(src_dir)
+        .expect("Unable to read source directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "md"))
+        .collect::<Vec<_>>();
+    chapters.sort();
+
+    let mut found_errs = false;
+    for chapter in chapters {
+        for err in check_chapter(src_dir, &chapter) {
+            found_errs = true;
+            match err {
+                CheckingError::UnableToOpenFile(path) => {
+                    eprintln!("Unable to open {}.", path.display())
+                }
+                CheckingError::CodeNotInOutput(line_num, code) => {
+                    eprintln!(
+                        "{}:{}\t{} is not produced by any included output.txt",
+                        chapter.display(),
+                        line_num,
+                        code
+                    )
+                }
+            }
+        }
+    }
+
+    if found_errs {
+        std::process::exit(1)
+    } else {
+        std::process::exit(0)
+    }
+}
+
+const USAGE: &str = "
+check_error_codes
+Usage:
+  check_error_codes <src-dir>
+  check_error_codes (-h | --help)
+Options:
+  -h --help         Show this screen.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+}
+
+lazy_static! {
+    static ref ERROR_CODE: Regex = Regex::new(r"\bE\d{4}\b").unwrap();
+    static ref OUTPUT_INCLUDE: Regex =
+        Regex::new(r"\{\{#include\s+([^}:\s]*output\.txt)(?::[^}]*)?\}\}")
+            .unwrap();
+}
+
+// Every error code mentioned in the chapter's prose has to show up in at least
+// one of the `output.txt` files the chapter includes. Codes inside fenced code
+// blocks are hand-written output and aren't checked.
+fn check_chapter(
+    src_dir: &path::Path,
+    chapter: &path::Path,
+) -> Vec<CheckingError> {
+    let contents = match fs::read_to_string(chapter) {
+        Ok(contents) => contents,
+        Err(_) => {
+            return vec![CheckingError::UnableToOpenFile(chapter.to_owned())]
+        }
+    };
+
+    let mut errs = Vec::new();
+    let mut produced_codes = BTreeSet::new();
+    for output_path in included_outputs(&contents) {
+        let output_path = src_dir.join(output_path);
+        match fs::read_to_string(&output_path) {
+            Ok(output) => produced_codes.extend(error_codes(&output)),
+            Err(_) => errs.push(CheckingError::UnableToOpenFile(output_path)),
+        }
+    }
+
+    for (line_num, code) in prose_error_codes(&contents) {
+        if !produced_codes.contains(&code) {
+            errs.push(CheckingError::CodeNotInOutput(line_num, code));
+        }
+    }
+
+    errs
+}
+
+fn included_outputs(contents: &str) -> Vec<String> {
+    OUTPUT_INCLUDE
+        .captures_iter(contents)
+        .map(|caps| caps[1].to_string())
+        .collect()
+}
+
+fn error_codes(text: &str) -> BTreeSet<String> {
+    ERROR_CODE
+        .find_iter(text)
+        .map(|code| code.as_str().to_string())
+        .collect()
+}
+
+// Returns the 1-based line number and code of every error code found outside
+// of fenced code blocks, including the ones in block quotes.
+fn prose_error_codes(contents: &str) -> Vec<(usize, String)> {
+    let mut open_fence: Option<&str> = None;
+    let mut codes = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let trimmed =
+            line.trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+        let fence = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| trimmed.starts_with(fence));
+        if let Some(fence) = fence {
+            match open_fence {
+                None => open_fence = Some(fence),
+                Some(open) if open == fence => open_fence = None,
+                Some(_) => {}
+            }
+            continue;
+        }
+        if open_fence.is_some() {
+            continue;
+        }
+        for code in ERROR_CODE.find_iter(line) {
+            codes.push((index + 1, code.as_str().to_string()));
+        }
+    }
+
+    codes
+}
+
+#[derive(Debug)]
+enum CheckingError {
+    UnableToOpenFile(path::PathBuf),
+    CodeNotInOutput(usize, String),
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn prose_error_codes_skips_fenced_code_blocks() {
+        let contents = r#"Rust 會回傳錯誤 `E0499`：
+
+```console
+error[E0502]: cannot borrow `s` as mutable because it is also borrowed as immutable
+```
+
+也就是 E0502 錯誤。
+"#;
+
+        let codes = super::prose_error_codes(contents);
+
+        assert_eq!(
+            codes,
+            vec![(1, "E0499".to_string()), (7, "E0502".to_string())]
+        );
+    }
+
+    #[test]
+    fn prose_error_codes_skips_fences_in_block_quotes() {
+        let contents = r#"> 注意：
+>
+> ```console
+> error[E0106]: missing lifetime specifier
+> ```
+
+~~~text
+error[E0382]: borrow of moved value
+```
+~~~
+"#;
+
+        assert!(super::prose_error_codes(contents).is_empty());
+    }
+
+    #[test]
+    fn prose_error_codes_ignores_longer_words() {
+        let contents = "E04990 與 XE0499 都不是錯誤碼";
+
+        assert!(super::prose_error_codes(contents).is_empty());
+    }
+
+    #[test]
+    fn included_outputs_finds_output_files_with_and_without_line_ranges() {
+        let contents = r#"```console
+{{#include ../listings/ch04-understanding-ownership/listing-04-06/output.txt}}
+```
+
+```text
+{{#include ../listings/ch05-using-structs-to-structure-related-data/listing-05-11/output.txt:9:10}}
+```
+
+```rust
+{{#rustdoc_include ../listings/ch04-understanding-ownership/listing-04-06/src/main.rs}}
+```
+"#;
+
+        assert_eq!(
+            super::included_outputs(contents),
+            vec![
+                "../listings/ch04-understanding-ownership/listing-04-06/output.txt",
+                "../listings/ch05-using-structs-to-structure-related-data/listing-05-11/output.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn error_codes_collects_codes_from_compiler_output() {
+        let output = r#"error[E0499]: cannot borrow `s` as mutable more than once at a time
+
+For more information about this error, try `rustc --explain E0499`.
+"#;
+
+        let codes = super::error_codes(output);
+
+        assert_eq!(codes.len(), 1);
+        assert!(codes.contains("E0499"));
+    }
+}
@@ -0,0 +1,456 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::{fs, path};
+
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let src_dir = path::Path::new(&args.arg_src_dir);
+    let mut chapters = fs::read_dir(src_dir)
+        .expect("Unable to read source directory")
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "md"))
+        .collect::<Vec<_>>();
+    chapters.sort();
+
+    let mut found_errs = false;
+    let mut captions = Vec::new();
+    for chapter in &chapters {
+        match fs::read_to_string(chapter) {
+            Ok(contents) => {
+                for caption in listing_captions(&contents) {
+                    captions.push((chapter.clone(), caption));
+                }
+            }
+            Err(_) => {
+                found_errs = true;
+                eprintln!("Unable to open {}.", chapter.display());
+            }
+        }
+    }
+
+    for (chapter, caption) in &captions {
+        let chapter_number = chapter_number(chapter);
+        match check_caption(chapter_number, caption) {
+            Ok(None) => {}
+            Ok(Some(note)) => {
+                println!("{}:{}\t{}", chapter.display(), caption.line, note)
+            }
+            Err(err) => {
+                found_errs = true;
+                eprintln!("{}:{}\t{}", chapter.display(), caption.line, err);
+            }
+        }
+    }
+
+    let numbering = captions
+        .iter()
+        .map(|(chapter, caption)| (chapter, caption.line, caption.number));
+    for (chapter, line, err) in check_numbering(numbering) {
+        found_errs = true;
+        eprintln!("{}:{}\t{}", chapter.display(), line, err);
+    }
+
+    // Listing directories are named after their caption; a number without a
+    // directory is only fine when its caption shows something else, like a
+    // file that lives with the listing that uses it
+    let listings_dir = src_dir.join("..").join("listings");
+    let captioned = captions
+        .iter()
+        .map(|(_, caption)| caption.number)
+        .collect::<BTreeSet<_>>();
+    for (chapter_dir, number) in missing_listing_dirs(&listings_dir) {
+        if !captioned.contains(&number) {
+            found_errs = true;
+            eprintln!(
+                "{}\tlisting-{:02}-{:02} is missing and no caption explains why",
+                chapter_dir.display(),
+                number.0,
+                number.1
+            );
+        }
+    }
+
+    if found_errs {
+        std::process::exit(1)
+    } else {
+        std::process::exit(0)
+    }
+}
+
+const USAGE: &str = "
+check_listing_numbers
+Usage:
+  check_listing_numbers <src-dir>
+  check_listing_numbers (-h | --help)
+Options:
+  -h --help         Show this screen.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+}
+
+lazy_static! {
+    static ref LISTING_INCLUDE: Regex = Regex::new(
+        r"\{\{#(?:rustdoc_)?include \.\./listings/[^/]+/([^/]+)/([^}:\s]*)"
+    )
+    .unwrap();
+    static ref MANUAL_REGENERATION: Regex =
+        Regex::new(r"^cd listings/[^/]+/([^/\s]+)").unwrap();
+    static ref CAPTION: Regex =
+        Regex::new(r#"^<span class="caption">範例 (\d+)-(\d+)"#).unwrap();
+    static ref LISTING_DIR: Regex =
+        Regex::new(r"^listing-(\d+)-(\d+)(?:-and-(\d+))?(-reproduced)?$")
+            .unwrap();
+    static ref CHAPTER_NUMBER: Regex = Regex::new(r"^ch(\d+)-").unwrap();
+}
+
+#[derive(Debug, PartialEq)]
+struct Caption {
+    // 1-based line number of the caption
+    line: usize,
+    // Chapter and listing number, `(12, 14)` for `範例 12-14`
+    number: (u32, u32),
+    // Listing directory and file of everything the code block above the
+    // caption includes
+    includes: Vec<(String, String)>,
+}
+
+// Every `範例` caption in the chapter, with what the code block right above it
+// includes. Output that's regenerated by hand is pasted in the code block
+// after the `manual-regeneration` comment saying which listing it comes from.
+fn listing_captions(contents: &str) -> Vec<Caption> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut captions = Vec::new();
+    let mut within_codeblock = false;
+    let mut includes = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        for caps in LISTING_INCLUDE.captures_iter(line) {
+            includes.push((caps[1].to_string(), caps[2].to_string()));
+        }
+        for caps in MANUAL_REGENERATION.captures_iter(line) {
+            includes.push((caps[1].to_string(), "output".to_string()));
+        }
+
+        if !line.trim_start().starts_with("```") {
+            continue;
+        }
+        within_codeblock = !within_codeblock;
+        if within_codeblock {
+            continue;
+        }
+
+        let caption = lines
+            .iter()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, line)| {
+                !line.trim().is_empty()
+                    && !line.starts_with(r#"<span class="filename">"#)
+            })
+            .and_then(|(caption_index, line)| {
+                CAPTION.captures(line).map(|caps| (caption_index, caps))
+            });
+        if let Some((caption_index, caps)) = caption {
+            captions.push(Caption {
+                line: caption_index + 1,
+                number: (caps[1].parse().unwrap(), caps[2].parse().unwrap()),
+                includes: includes.clone(),
+            });
+        }
+        includes.clear();
+    }
+
+    captions
+}
+
+fn chapter_number(chapter: &path::Path) -> Option<u32> {
+    let file_name = chapter.file_name()?.to_str()?;
+    CHAPTER_NUMBER.captures(file_name)?[1].parse().ok()
+}
+
+// The numbers a listing directory is named after: `listing-07-21-and-22` has
+// two and `no-listing-*` directories have none.
+fn listing_dir_numbers(dir: &str) -> Vec<(u32, u32)> {
+    let caps = match LISTING_DIR.captures(dir) {
+        Some(caps) => caps,
+        None => return Vec::new(),
+    };
+    let chapter = caps[1].parse().unwrap();
+    let mut numbers = vec![(chapter, caps[2].parse().unwrap())];
+    if let Some(second) = caps.get(3) {
+        numbers.push((chapter, second.as_str().parse().unwrap()));
+    }
+    numbers
+}
+
+// A caption has to be right below the listing directory with the same number,
+// unless the code isn't from a listing at all. Listings from earlier that are
+// shown again, and files like `hello.html` or the output of a listing that get
+// their own caption but live in the directory of the listing that uses them,
+// get a note.
+fn check_caption(
+    chapter_number: Option<u32>,
+    caption: &Caption,
+) -> Result<Option<String>, CheckingError> {
+    let (chapter, number) = caption.number;
+    if chapter_number.map_or(false, |expected| expected != chapter) {
+        return Err(CheckingError::WrongChapter(caption.number));
+    }
+
+    let (dir, file) = match caption.includes.first() {
+        Some(include) => include,
+        None => return Ok(None),
+    };
+    if caption.includes.iter().any(|(dir, _)| {
+        !dir.ends_with("-reproduced")
+            && listing_dir_numbers(dir).contains(&caption.number)
+    }) {
+        return Ok(None);
+    }
+
+    let dir_numbers = listing_dir_numbers(dir);
+    if dir_numbers.is_empty() {
+        return Err(CheckingError::NotAListing(caption.number, dir.clone()));
+    }
+    if dir.ends_with("-reproduced") || dir_numbers[0].0 != chapter {
+        return Ok(Some(format!(
+            "範例 {}-{} shows {} again",
+            chapter, number, dir
+        )));
+    }
+    let shows_supporting_files = caption
+        .includes
+        .iter()
+        .all(|(_, file)| !file.ends_with(".rs"));
+    if shows_supporting_files {
+        return Ok(Some(format!(
+            "範例 {}-{} shows {} of {}",
+            chapter, number, file, dir
+        )));
+    }
+
+    Err(CheckingError::WrongListing(caption.number, dir.clone()))
+}
+
+// Captions are numbered from 1 within each chapter, across all of its
+// sections.
+fn check_numbering<'a, C: 'a>(
+    captions: impl Iterator<Item = (C, usize, (u32, u32))>,
+) -> Vec<(C, usize, CheckingError)> {
+    let mut errs = Vec::new();
+    let mut previous: BTreeMap<u32, u32> = BTreeMap::new();
+
+    for (chapter, line, (chapter_number, number)) in captions {
+        let last = previous.get(&chapter_number).copied().unwrap_or(0);
+        if number <= last {
+            errs.push((
+                chapter,
+                line,
+                CheckingError::OutOfOrder((chapter_number, number), last),
+            ));
+            continue;
+        }
+        if number > last + 1 {
+            errs.push((
+                chapter,
+                line,
+                CheckingError::Gap((chapter_number, number), last),
+            ));
+        }
+        previous.insert(chapter_number, number);
+    }
+
+    errs
+}
+
+// The numbers missing from the `listing-NN-MM` directories of each chapter,
+// below the highest one.
+fn missing_listing_dirs(
+    listings_dir: &path::Path,
+) -> Vec<(path::PathBuf, (u32, u32))> {
+    let mut missing = Vec::new();
+    let mut chapter_dirs = match fs::read_dir(listings_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>(),
+        Err(_) => return missing,
+    };
+    chapter_dirs.sort();
+
+    for chapter_dir in chapter_dirs {
+        let numbers = fs::read_dir(&chapter_dir)
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter(|name| !name.ends_with("-reproduced"))
+            .flat_map(|name| listing_dir_numbers(&name))
+            .collect::<BTreeSet<(u32, u32)>>();
+        let (chapter, highest) = match numbers.iter().next_back() {
+            Some(number) => *number,
+            None => continue,
+        };
+        for number in 1..highest {
+            if !numbers.contains(&(chapter, number)) {
+                missing.push((chapter_dir.clone(), (chapter, number)));
+            }
+        }
+    }
+
+    missing
+}
+
+#[derive(Debug, PartialEq)]
+enum CheckingError {
+    NotAListing((u32, u32), String),
+    WrongListing((u32, u32), String),
+    WrongChapter((u32, u32)),
+    OutOfOrder((u32, u32), u32),
+    Gap((u32, u32), u32),
+}
+
+impl std::fmt::Display for CheckingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CheckingError::NotAListing((chapter, number), dir) => write!(
+                f,
+                "範例 {}-{} points at {}, which isn't a numbered listing",
+                chapter, number, dir
+            ),
+            CheckingError::WrongListing((chapter, number), dir) => write!(
+                f,
+                "範例 {}-{} includes {} instead of listing-{:02}-{:02}",
+                chapter, number, dir, chapter, number
+            ),
+            CheckingError::WrongChapter((chapter, number)) => write!(
+                f,
+                "範例 {}-{} is in another chapter's section",
+                chapter, number
+            ),
+            CheckingError::OutOfOrder((chapter, number), last) => write!(
+                f,
+                "範例 {}-{} comes after 範例 {}-{}",
+                chapter, number, chapter, last
+            ),
+            CheckingError::Gap((chapter, number), last) => write!(
+                f,
+                "範例 {}-{} follows 範例 {}-{}, skipping numbers",
+                chapter, number, chapter, last
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Caption, CheckingError};
+
+    #[test]
+    fn listing_captions_pairs_captions_with_the_code_block_above() {
+        let contents = r#"<span class="filename">檔案名稱：src/main.rs</span>
+
+```rust,ignore
+{{#rustdoc_include ../listings/ch12-an-io-project/listing-12-14/src/main.rs:here}}
+```
+
+<span class="caption">範例 12-14：在 *src/main.rs* 使用 `minigrep` 函式庫 crate</span>
+
+```console
+{{#include ../listings/ch12-an-io-project/listing-12-14/output.txt}}
+```
+"#;
+
+        assert_eq!(
+            super::listing_captions(contents),
+            vec![Caption {
+                line: 7,
+                number: (12, 14),
+                includes: vec![(
+                    "listing-12-14".to_string(),
+                    "src/main.rs".to_string()
+                )],
+            }]
+        );
+    }
+
+    #[test]
+    fn check_caption_flags_no_listing_directories() {
+        let caption = Caption {
+            line: 1,
+            number: (12, 24),
+            includes: vec![(
+                "no-listing-02-using-search-in-run".to_string(),
+                "src/lib.rs".to_string(),
+            )],
+        };
+
+        assert_eq!(
+            super::check_caption(Some(12), &caption),
+            Err(CheckingError::NotAListing(
+                (12, 24),
+                "no-listing-02-using-search-in-run".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn check_caption_explains_supporting_files_of_another_listing() {
+        let caption = Caption {
+            line: 1,
+            number: (20, 4),
+            includes: vec![(
+                "listing-20-05".to_string(),
+                "hello.html".to_string(),
+            )],
+        };
+
+        assert!(matches!(
+            super::check_caption(Some(20), &caption),
+            Ok(Some(_))
+        ));
+    }
+
+    #[test]
+    fn check_numbering_reports_gaps_and_out_of_order_captions() {
+        let captions = vec![
+            ("ch08-01", 10, (8, 1)),
+            ("ch08-01", 20, (8, 3)),
+            ("ch08-02", 5, (8, 2)),
+            ("ch09-01", 5, (9, 1)),
+        ];
+
+        assert_eq!(
+            super::check_numbering(captions.into_iter()),
+            vec![
+                ("ch08-01", 20, CheckingError::Gap((8, 3), 1)),
+                ("ch08-02", 5, CheckingError::OutOfOrder((8, 2), 3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn listing_dir_numbers_reads_directories_with_two_listings() {
+        assert_eq!(
+            super::listing_dir_numbers("listing-07-21-and-22"),
+            vec![(7, 21), (7, 22)]
+        );
+        assert_eq!(super::listing_dir_numbers("listing-20-05"), vec![(20, 5)]);
+        assert!(
+            super::listing_dir_numbers("no-listing-01-cargo-new").is_empty()
+        );
+    }
+}
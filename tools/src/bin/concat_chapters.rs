@@ -1,88 +1,206 @@
 #[macro_use]
 extern crate lazy_static;
 
-use std::collections::BTreeMap;
-use std::env;
-use std::fs::{create_dir, read_dir, File};
+use std::collections::HashMap;
+use std::fs::{self, create_dir, File};
 use std::io;
-use std::io::{Read, Write};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::exit;
 
+use docopt::Docopt;
 use regex::Regex;
+use serde::Deserialize;
 
-static PATTERNS: &'static [(&'static str, &'static str)] = &[
-    (r"ch(\d\d)-\d\d-.*\.md", "chapter$1.md"),
-    (r"appendix-(\d\d).*\.md", "appendix.md"),
-];
+fn main() {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
 
-lazy_static! {
-    static ref MATCHERS: Vec<(Regex, &'static str)> = {
-        PATTERNS
+    let source_dir = ensure_dir_exists(&args.arg_src_dir).unwrap();
+    let target_dir = ensure_dir_exists(&args.arg_target_dir).unwrap();
+
+    let summary = fs::read_to_string(&args.flag_summary)
+        .expect("Unable to read SUMMARY.md");
+    let items = summary_items(&summary);
+    warn_about_unlisted_files(source_dir, &items);
+
+    for group in group_items(&items, &args.flag_group_by) {
+        // `nostarch.sh` can be asked to only transform some of the files
+        let source_paths = group
+            .files
             .iter()
-            .map(|&(expr, repl)| (Regex::new(expr).unwrap(), repl))
-            .collect()
-    };
+            .map(|file| source_dir.join(file))
+            .filter(|path| path.exists())
+            .collect::<Vec<_>>();
+        if source_paths.is_empty() {
+            continue;
+        }
+        concat_files(source_paths, target_dir.join(&group.name)).unwrap();
+    }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+const USAGE: &str = "
+concat_chapters
+Concatenates the Markdown files in <src-dir> into one file per chapter, in the
+order of `SUMMARY.md`. Files before the first chapter go into `frontmatter.md`.
 
-    if args.len() < 3 {
-        println!("Usage: {} <src-dir> <target-dir>", args[0]);
-        exit(1);
-    }
+Usage:
+  concat_chapters [options] <src-dir> <target-dir>
+  concat_chapters (-h | --help)
 
-    let source_dir = ensure_dir_exists(&args[1]).unwrap();
-    let target_dir = ensure_dir_exists(&args[2]).unwrap();
+Options:
+  -h --help            Show this screen.
+  --summary=<file>     The book's summary [default: src/SUMMARY.md].
+  --group-by=<group>   `chapter` for `chapter01.md` and so on, `part` for
+                       `part01.md` and so on, following the `##` part titles,
+                       or `book` for a single `book.md` [default: chapter].
+";
 
-    let mut matched_files = match_files(source_dir, target_dir);
-    matched_files.sort();
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+    arg_target_dir: String,
+    flag_summary: String,
+    flag_group_by: Grouping,
+}
 
-    for (target_path, source_paths) in group_by_target(matched_files) {
-        concat_files(source_paths, target_path).unwrap();
-    }
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Grouping {
+    Part,
+    Chapter,
+    Book,
 }
 
-fn match_files(
-    source_dir: &Path,
-    target_dir: &Path,
-) -> Vec<(PathBuf, PathBuf)> {
-    read_dir(source_dir)
-        .expect("Unable to read source directory")
-        .filter_map(|maybe_entry| maybe_entry.ok())
-        .filter_map(|entry| {
-            let source_filename = entry.file_name();
-            let source_filename =
-                &source_filename.to_string_lossy().into_owned();
-            for &(ref regex, replacement) in MATCHERS.iter() {
-                if regex.is_match(source_filename) {
-                    let target_filename =
-                        regex.replace_all(source_filename, replacement);
-                    let source_path = entry.path();
-                    let mut target_path = PathBuf::from(&target_dir);
-                    target_path.push(target_filename.to_string());
-                    return Some((source_path, target_path));
-                }
+// What each line of `SUMMARY.md` adds to the book.
+#[derive(Debug, PartialEq)]
+enum SummaryItem {
+    // A `## ` title starting a part of the book
+    Part,
+    // A link outside the list of chapters, like the foreword
+    Unnumbered(String),
+    // A top-level list item
+    Chapter(String),
+    // A nested list item
+    Section(String),
+}
+
+lazy_static! {
+    static ref SUMMARY_LINK: Regex =
+        Regex::new(r"^(\s*)(-\s+)?\[.*\]\(([^)]+)\)").unwrap();
+    static ref PART_TITLE: Regex = Regex::new(r"^##+\s").unwrap();
+}
+
+fn summary_items(summary: &str) -> Vec<SummaryItem> {
+    summary
+        .lines()
+        .filter_map(|line| {
+            if PART_TITLE.is_match(line) {
+                return Some(SummaryItem::Part);
             }
-            None
+            let caps = SUMMARY_LINK.captures(line)?;
+            let file = caps[3].to_string();
+            Some(match (caps.get(2), caps[1].is_empty()) {
+                (None, _) => SummaryItem::Unnumbered(file),
+                (Some(_), true) => SummaryItem::Chapter(file),
+                (Some(_), false) => SummaryItem::Section(file),
+            })
         })
         .collect()
 }
 
-fn group_by_target(
-    matched_files: Vec<(PathBuf, PathBuf)>,
-) -> BTreeMap<PathBuf, Vec<PathBuf>> {
-    let mut grouped: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
-    for (source, target) in matched_files {
-        if let Some(source_paths) = grouped.get_mut(&target) {
-            source_paths.push(source);
-            continue;
+// An output file and the source files that go into it, in order.
+#[derive(Debug, PartialEq)]
+struct Group {
+    name: String,
+    files: Vec<String>,
+}
+
+fn group_items(items: &[SummaryItem], grouping: &Grouping) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut parts = 0;
+    let mut seen_chapter = false;
+
+    for item in items {
+        let new_group = match (item, grouping) {
+            (SummaryItem::Part, Grouping::Part) => {
+                parts += 1;
+                Some(format!("part{:02}.md", parts))
+            }
+            (SummaryItem::Chapter(file), Grouping::Chapter) => {
+                Some(chapter_file_name(file))
+            }
+            (SummaryItem::Unnumbered(_), Grouping::Chapter) if seen_chapter => {
+                Some(String::from("backmatter.md"))
+            }
+            // Whatever comes before the first part or chapter
+            _ if groups.is_empty() => Some(String::from(match grouping {
+                Grouping::Book => "book.md",
+                _ => "frontmatter.md",
+            })),
+            _ => None,
+        };
+        if let Some(name) = new_group {
+            // Consecutive unnumbered files share a file
+            if groups.last().map_or(true, |group| group.name != name) {
+                groups.push(Group {
+                    name,
+                    files: Vec::new(),
+                });
+            }
         }
-        let source_paths = vec![source];
-        grouped.insert(target.clone(), source_paths);
+
+        match item {
+            SummaryItem::Part => {}
+            SummaryItem::Chapter(file)
+            | SummaryItem::Section(file)
+            | SummaryItem::Unnumbered(file) => {
+                seen_chapter |= matches!(item, SummaryItem::Chapter(_));
+                if let Some(group) = groups.last_mut() {
+                    group.files.push(file.clone());
+                }
+            }
+        }
+    }
+
+    groups
+}
+
+lazy_static! {
+    static ref CHAPTER_FILE: Regex = Regex::new(r"^ch(\d\d)-").unwrap();
+}
+
+// `chapter01.md` for the files of chapter 1 and `appendix.md` for the
+// appendix, like the print manuscript has always been split.
+fn chapter_file_name(file: &str) -> String {
+    if let Some(caps) = CHAPTER_FILE.captures(file) {
+        format!("chapter{}.md", &caps[1])
+    } else if file.starts_with("appendix-") {
+        String::from("appendix.md")
+    } else {
+        file.to_string()
+    }
+}
+
+fn warn_about_unlisted_files(source_dir: &Path, items: &[SummaryItem]) {
+    let mut unlisted = fs::read_dir(source_dir)
+        .expect("Unable to read source directory")
+        .filter_map(|maybe_entry| maybe_entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|file| file.ends_with(".md") && file != "SUMMARY.md")
+        .filter(|file| {
+            !items.iter().any(|item| match item {
+                SummaryItem::Part => false,
+                SummaryItem::Chapter(listed)
+                | SummaryItem::Section(listed)
+                | SummaryItem::Unnumbered(listed) => listed == file,
+            })
+        })
+        .collect::<Vec<_>>();
+    unlisted.sort();
+    for file in unlisted {
+        eprintln!("Skipping {}, which isn't in SUMMARY.md", file);
     }
-    grouped
 }
 
 fn concat_files(
@@ -92,31 +210,282 @@ fn concat_files(
     println!("Concatenating into {}:", target_path.to_string_lossy());
     let mut target = File::create(target_path)?;
 
-    write!(target, "\
+    let mut sources = Vec::new();
+    for path in &source_paths {
+        println!("  {}", path.to_string_lossy());
+        sources.push(fs::read_to_string(path)?);
+    }
+
+    let mut anchors = Anchors::default();
+    let mut toc = Vec::new();
+    let bodies = sources
+        .iter()
+        .map(|contents| add_anchors(contents, &mut anchors, &mut toc))
+        .collect::<Vec<_>>();
+
+    write!(
+        target,
+        "\
 <!-- DO NOT EDIT THIS FILE.
 This file is periodically generated from the content in the `/src/`
-directory, so all fixes need to be made in `/src/`.
--->
-[TOC]
-")?;
-
-    for path in source_paths {
-        println!("  {}", path.to_string_lossy());
-        let mut source = File::open(path)?;
-        let mut contents: Vec<u8> = Vec::new();
-        source.read_to_end(&mut contents)?;
+directory, so all fixes need to be made in `/src/`. It was generated from:
+"
+    )?;
+    for path in &source_paths {
+        let file_name = path.file_name().expect("Source should be a file");
+        writeln!(target, "/src/{}", file_name.to_string_lossy())?;
+    }
+    writeln!(target, "-->")?;
+    target.write_all(render_toc(&toc).as_bytes())?;
 
+    for body in bodies {
         target.write_all(b"\n")?;
-        target.write_all(&contents)?;
+        target.write_all(body.as_bytes())?;
         target.write_all(b"\n")?;
     }
     Ok(())
 }
 
+// Headings deeper than this are left out of the table of contents.
+const TOC_DEPTH: usize = 3;
+
+lazy_static! {
+    static ref HEADING: Regex =
+        Regex::new(r"^(#{1,6})\s+(.*?)(\s+#+)?\s*$").unwrap();
+}
+
+// A heading in the table of contents.
+#[derive(Debug, PartialEq)]
+struct TocEntry {
+    level: usize,
+    text: String,
+    id: String,
+}
+
+// The anchor IDs used so far, so every heading gets a unique one.
+#[derive(Debug, Default)]
+struct Anchors {
+    used: HashMap<String, usize>,
+}
+
+impl Anchors {
+    // Like mdBook and GitHub do, keep letters and digits, which in Rust
+    // include the CJK characters, and turn spaces into dashes. Repeated
+    // headings get `-1`, `-2` and so on, in the order they appear, so the
+    // IDs only change when the headings do.
+    fn id_for(&mut self, heading: &str) -> String {
+        let base = heading
+            .trim()
+            .chars()
+            .filter_map(|c| {
+                if c.is_alphanumeric() || c == '_' || c == '-' {
+                    Some(c.to_lowercase().next().unwrap_or(c))
+                } else if c.is_whitespace() {
+                    Some('-')
+                } else {
+                    None
+                }
+            })
+            .collect::<String>();
+        let count = self.used.entry(base.clone()).or_insert(0);
+        let id = if *count == 0 {
+            base
+        } else {
+            format!("{}-{}", base, count)
+        };
+        *count += 1;
+        id
+    }
+}
+
+// Puts an explicit anchor before every heading outside code blocks, since not
+// every Markdown viewer makes IDs for headings, and the ones that do disagree
+// about CJK text.
+fn add_anchors(
+    contents: &str,
+    anchors: &mut Anchors,
+    toc: &mut Vec<TocEntry>,
+) -> String {
+    let mut output = String::with_capacity(contents.len());
+    let mut in_code_block = false;
+    for line in contents.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if let Some(caps) = HEADING.captures(line).filter(|_| !in_code_block) {
+            let text = caps[2].to_string();
+            let id = anchors.id_for(&text);
+            output.push_str(&format!("<a id=\"{}\"></a>\n\n", id));
+            let level = caps[1].len();
+            if level <= TOC_DEPTH {
+                toc.push(TocEntry { level, text, id });
+            }
+        }
+        output.push_str(line);
+        output.push('\n');
+    }
+    output
+}
+
+fn render_toc(toc: &[TocEntry]) -> String {
+    let top_level = toc.iter().map(|entry| entry.level).min().unwrap_or(1);
+    toc.iter()
+        .map(|entry| {
+            format!(
+                "{}- [{}](#{})\n",
+                "  ".repeat(entry.level - top_level),
+                entry.text,
+                entry.id
+            )
+        })
+        .collect()
+}
+
 fn ensure_dir_exists(dir_string: &str) -> io::Result<&Path> {
     let path = Path::new(dir_string);
     if !path.exists() {
         create_dir(path)?;
     }
-    Ok(&path)
-}
\ No newline at end of file
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchors, Group, Grouping, SummaryItem, TocEntry};
+
+    const SUMMARY: &str = "# Rust 程式設計語言
+
+[Rust 程式設計語言](title-page.md)
+[前言](foreword.md)
+[介紹](ch00-00-introduction.md)
+
+## 開始入門
+
+- [開始入門](ch01-00-getting-started.md)
+    - [安裝教學](ch01-01-installation.md)
+
+- [設計猜謎遊戲程式](ch02-00-guessing-game-tutorial.md)
+
+## 進階主題
+
+- [附錄](appendix-00.md)
+    - [A - 關鍵字](appendix-01-keywords.md)
+";
+
+    fn group(name: &str, files: &[&str]) -> Group {
+        Group {
+            name: name.to_string(),
+            files: files.iter().map(|file| file.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn summary_items_tells_chapters_from_sections_and_front_matter() {
+        let items = super::summary_items(SUMMARY);
+
+        assert_eq!(
+            &items[..5],
+            &[
+                SummaryItem::Unnumbered("title-page.md".to_string()),
+                SummaryItem::Unnumbered("foreword.md".to_string()),
+                SummaryItem::Unnumbered("ch00-00-introduction.md".to_string()),
+                SummaryItem::Part,
+                SummaryItem::Chapter("ch01-00-getting-started.md".to_string()),
+            ]
+        );
+        assert_eq!(
+            items[5],
+            SummaryItem::Section("ch01-01-installation.md".to_string())
+        );
+    }
+
+    #[test]
+    fn group_items_groups_by_chapter() {
+        let items = super::summary_items(SUMMARY);
+
+        assert_eq!(
+            super::group_items(&items, &Grouping::Chapter),
+            vec![
+                group(
+                    "frontmatter.md",
+                    &[
+                        "title-page.md",
+                        "foreword.md",
+                        "ch00-00-introduction.md"
+                    ]
+                ),
+                group(
+                    "chapter01.md",
+                    &["ch01-00-getting-started.md", "ch01-01-installation.md"]
+                ),
+                group("chapter02.md", &["ch02-00-guessing-game-tutorial.md"]),
+                group(
+                    "appendix.md",
+                    &["appendix-00.md", "appendix-01-keywords.md"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn group_items_groups_by_part_or_into_one_book() {
+        let items = super::summary_items(SUMMARY);
+
+        let parts = super::group_items(&items, &Grouping::Part);
+        let names: Vec<_> =
+            parts.iter().map(|part| part.name.as_str()).collect();
+        assert_eq!(names, vec!["frontmatter.md", "part01.md", "part02.md"]);
+        assert_eq!(parts[1].files.len(), 3);
+
+        let book = super::group_items(&items, &Grouping::Book);
+        assert_eq!(book.len(), 1);
+        assert_eq!(book[0].name, "book.md");
+        assert_eq!(book[0].files.len(), 8);
+    }
+
+    #[test]
+    fn anchors_keep_cjk_text_and_number_repeated_headings() {
+        let mut anchors = Anchors::default();
+
+        assert_eq!(anchors.id_for("什麼是所有權？"), "什麼是所有權");
+        assert_eq!(
+            anchors.id_for("`match` 控制流建構子"),
+            "match-控制流建構子"
+        );
+        assert_eq!(anchors.id_for("Hello, Cargo!"), "hello-cargo");
+        assert_eq!(anchors.id_for("總結"), "總結");
+        assert_eq!(anchors.id_for("總結"), "總結-1");
+    }
+
+    #[test]
+    fn add_anchors_skips_code_blocks_and_collects_the_toc() {
+        let mut anchors = Anchors::default();
+        let mut toc = Vec::new();
+        let contents = "# 開始入門\n\n```console\n# 這不是標題\n```\n\n## 安裝教學\n\n#### 疑難排除\n";
+
+        let output = super::add_anchors(contents, &mut anchors, &mut toc);
+
+        assert!(output.starts_with("<a id=\"開始入門\"></a>\n\n# 開始入門\n"));
+        assert!(!output.contains("這不是標題\"></a>"));
+        assert!(output.contains("<a id=\"疑難排除\"></a>\n\n#### 疑難排除\n"));
+        assert_eq!(
+            toc,
+            vec![
+                TocEntry {
+                    level: 1,
+                    text: "開始入門".to_string(),
+                    id: "開始入門".to_string(),
+                },
+                TocEntry {
+                    level: 2,
+                    text: "安裝教學".to_string(),
+                    id: "安裝教學".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            super::render_toc(&toc),
+            "- [開始入門](#開始入門)\n  - [安裝教學](#安裝教學)\n"
+        );
+    }
+}
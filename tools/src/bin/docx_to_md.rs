@@ -0,0 +1,441 @@
+use docopt::Docopt;
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use unicode_width::UnicodeWidthChar;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let files = if args.arg_docx.is_empty() {
+        docx_files_in(Path::new("tmp"))?
+    } else {
+        args.arg_docx.iter().map(PathBuf::from).collect()
+    };
+
+    let output_dir = Path::new(&args.flag_output_dir);
+    fs::create_dir_all(output_dir)?;
+
+    for path in files {
+        let xml = read_document_xml(&path)?;
+        let markdown = document_to_markdown(&xml)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let markdown = wrap(&markdown, args.flag_width);
+
+        let name = path
+            .file_stem()
+            .ok_or_else(|| format!("{} has no file name", path.display()))?;
+        let target = output_dir.join(name).with_extension("md");
+        fs::write(&target, markdown)?;
+        println!("{} -> {}", path.display(), target.display());
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "
+docx_to_md
+Converts the `.docx` files No Starch sends back into Markdown in the
+`nostarch` dir, so they can be compared with what we sent them. Without any
+<docx> files, every `.docx` file in the `tmp` dir is converted.
+
+Usage:
+  docx_to_md [options] [<docx>...]
+  docx_to_md (-h | --help)
+
+Options:
+  -h --help               Show this screen.
+  --output-dir=<dir>      Where to write the Markdown [default: nostarch].
+  --width=<columns>       Wrap lines at this many columns, counting CJK
+                          characters as two, or 0 not to wrap [default: 80].
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_docx: Vec<String>,
+    flag_output_dir: String,
+    flag_width: usize,
+}
+
+const W: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+
+fn docx_files_in(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "docx") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_document_xml(path: &Path) -> Result<String, Box<dyn Error>> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)?;
+    let mut xml = String::new();
+    archive
+        .by_name("word/document.xml")?
+        .read_to_string(&mut xml)?;
+    Ok(xml)
+}
+
+fn document_to_markdown(xml: &str) -> Result<String, roxmltree::Error> {
+    let doc = Document::parse(xml)?;
+    let mut out = String::new();
+    if let Some(body) = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name((W, "body")))
+    {
+        convert_children(body, &mut out);
+    }
+    Ok(out)
+}
+
+// Paragraphs can be nested in tables and such, which are otherwise ignored
+fn convert_children(node: Node, out: &mut String) {
+    for child in node.children().filter(Node::is_element) {
+        if child.has_tag_name((W, "p")) {
+            convert_paragraph(child, out);
+        } else {
+            convert_children(child, out);
+        }
+    }
+}
+
+fn child<'a, 'input>(
+    node: Node<'a, 'input>,
+    name: &str,
+) -> Option<Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name((W, name)))
+}
+
+fn style_of(properties: Option<Node>, name: &str) -> Option<String> {
+    properties
+        .and_then(|properties| child(properties, name))
+        .map(|style| style.attribute((W, "val")).unwrap_or("").to_string())
+}
+
+fn convert_paragraph(p: Node, out: &mut String) {
+    let properties = child(p, "pPr");
+    let style = match (properties, style_of(properties, "pStyle")) {
+        // Paragraphs with properties but no style are plain Word paragraphs
+        (Some(_), None) => return,
+        (None, _) => String::new(),
+        (_, Some(style)) => style,
+    };
+
+    match style.as_str() {
+        style
+            if style.starts_with("TOC")
+                || style.starts_with("Contents1")
+                || style.starts_with("Contents2")
+                || style.starts_with("Contents3") => {}
+        "ChapterStart" | "Normal" | "Standard" | "AuthorQuery" => {}
+        "ChapterTitle" => {
+            out.push_str("\n[TOC]\n\n# ");
+            convert_runs(p, out);
+            out.push_str("\n\n");
+        }
+        "HeadA" => heading(p, "##", out),
+        "HeadB" => heading(p, "###", out),
+        "HeadC" => heading(p, "####", out),
+        "HeadBox" => heading(p, "###", out),
+        "NumListA" | "NumListB" => list_item(p, "1. ", "\n", out),
+        "NumListC" => list_item(p, "1. ", "\n\n", out),
+        "BulletA" | "BulletB" | "ListPlainA" | "ListPlainB" => {
+            list_item(p, "* ", "\n", out)
+        }
+        "BulletC" | "ListPlainC" => list_item(p, "* ", "\n\n", out),
+        "SubBullet" => list_item(p, "  * ", "\n", out),
+        "BodyFirst" | "Body" | "BodyFirstBox" | "BodyBox" | "1stPara" => {
+            if p.descendants().any(|node| node.has_tag_name((W, "t"))) {
+                convert_runs(p, out);
+                out.push_str("\n\n");
+            }
+        }
+        "CodeA" | "CodeAWingding" => {
+            out.push_str("```\n");
+            code_runs(p, out);
+            out.push('\n');
+        }
+        "CodeB" | "CodeBWingding" => {
+            code_runs(p, out);
+            out.push('\n');
+        }
+        "CodeC" | "CodeCWingding" => {
+            code_runs(p, out);
+            out.push_str("\n```\n\n");
+        }
+        "CodeSingle" => {
+            out.push_str("```\n");
+            convert_runs(p, out);
+            out.push_str("\n```\n\n");
+        }
+        "ProductionDirective"
+        | "Caption"
+        | "TableTitle"
+        | "Caption1"
+        | "Listing" => {
+            convert_runs(p, out);
+            out.push_str("\n\n");
+        }
+        "BlockQuote" => {
+            out.push_str("> ");
+            convert_runs(p, out);
+        }
+        "BlockText" => {
+            out.push_str("\n> ");
+            convert_runs(p, out);
+            out.push_str("\n\n");
+        }
+        "Note" => {
+            out.push_str("> ");
+            convert_runs(p, out);
+            out.push_str("\n\n");
+        }
+        // The XSL indented whatever came next by the spaces after its
+        // `xsl:text`, which `sed` didn't remove
+        style => {
+            out.push_str("\nUnmatched: ");
+            out.push_str(style);
+            out.push_str("\n      ");
+        }
+    }
+}
+
+fn heading(p: Node, marker: &str, out: &mut String) {
+    out.push_str(marker);
+    out.push(' ');
+    convert_runs(p, out);
+    out.push_str("\n\n");
+}
+
+fn list_item(p: Node, marker: &str, end: &str, out: &mut String) {
+    out.push_str(marker);
+    convert_runs(p, out);
+    out.push_str(end);
+}
+
+// The text of the first `w:t` of a run, like XSLT's `value-of` gives
+fn run_text<'a>(run: Node<'a, '_>) -> &'a str {
+    child(run, "t").and_then(|t| t.text()).unwrap_or("")
+}
+
+// Runs in code paragraphs are taken as they are, without character styles
+fn code_runs(p: Node, out: &mut String) {
+    for run in p.children().filter(|node| node.has_tag_name((W, "r"))) {
+        out.push_str(run_text(run));
+    }
+}
+
+// Runs can also be nested, in hyperlinks or tracked changes for example
+fn convert_runs(node: Node, out: &mut String) {
+    for child in node.children().filter(Node::is_element) {
+        if child.has_tag_name((W, "r")) {
+            convert_run(child, out);
+        } else if !child.has_tag_name((W, "pPr")) {
+            convert_runs(child, out);
+        }
+    }
+}
+
+fn convert_run(run: Node, out: &mut String) {
+    let text = run_text(run);
+    match style_of(child(run, "rPr"), "rStyle").as_deref() {
+        Some("Literal" | "LiteralBold" | "LiteralCaption" | "LiteralBox") => {
+            wrap_run(text, "`", false, out)
+        }
+        Some("EmphasisBold") => wrap_run(text, "**", false, out),
+        Some(
+            "EmphasisItalic" | "EmphasisItalicBox" | "EmphasisNote"
+            | "EmphasisRevCaption" | "EmphasisRevItal",
+        ) => wrap_run(text, "*", true, out),
+        _ => out.push_str(text),
+    }
+}
+
+// Puts `marker` around the text of a run, keeping one space on either side
+// outside the markers, where Markdown needs them. Runs of only whitespace
+// become a single space; `always_space` makes empty runs one too. Like
+// XPath's `normalize-space`, only ASCII whitespace counts, so full-width
+// spaces are kept.
+fn wrap_run(text: &str, marker: &str, always_space: bool, out: &mut String) {
+    let normalized = text
+        .split(|c| matches!(c, ' ' | '\t' | '\r' | '\n'))
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if !normalized.is_empty() {
+        if text.starts_with(' ') {
+            out.push(' ');
+        }
+        out.push_str(marker);
+        out.push_str(&normalized);
+        out.push_str(marker);
+        if text.ends_with(' ') {
+            out.push(' ');
+        }
+    } else if always_space || !text.is_empty() {
+        out.push(' ');
+    }
+}
+
+// Hard wraps lines longer than `width` columns and removes trailing spaces.
+// Code blocks and headings are left alone, since wrapping them would change
+// what they mean.
+fn wrap(markdown: &str, width: usize) -> String {
+    let mut out = String::new();
+    let mut in_code_block = false;
+    for line in markdown.lines() {
+        let is_fence = line.starts_with("```");
+        if width == 0 || in_code_block || is_fence || line.starts_with('#') {
+            out.push_str(line.trim_end_matches(' '));
+            out.push('\n');
+        } else {
+            for part in wrap_line(line, width) {
+                out.push_str(part.trim_end_matches(' '));
+                out.push('\n');
+            }
+        }
+        if is_fence {
+            in_code_block = !in_code_block;
+        }
+    }
+    out
+}
+
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut end = start;
+        let mut columns = 0;
+        while end < chars.len() {
+            let char_width = chars[end].width().unwrap_or(0);
+            if columns + char_width > width {
+                break;
+            }
+            columns += char_width;
+            end += 1;
+        }
+        if end == chars.len() {
+            parts.push(chars[start..].iter().collect());
+            return parts;
+        }
+        // Like `fold -s`, break after the last space that fits, but also
+        // between CJK characters, which aren't separated by spaces. Without
+        // either, break wherever the line is full.
+        let end = (start + 1..=end)
+            .rev()
+            .find(|&i| can_break(chars[i - 1], chars[i]))
+            .unwrap_or_else(|| end.max(start + 1));
+        parts.push(chars[start..end].iter().collect());
+        start = end;
+    }
+}
+
+// Punctuation that mustn't start a line
+const NO_BREAK_BEFORE: &str = "，。、；：？！）」』》〉】…—～,.;:?!)]}";
+// Punctuation that mustn't end a line
+const NO_BREAK_AFTER: &str = "（「『《〈【([{";
+
+fn can_break(before: char, after: char) -> bool {
+    if before == ' ' {
+        return after != ' ';
+    }
+    if after == ' ' {
+        return false;
+    }
+    (is_wide(before) || is_wide(after))
+        && !NO_BREAK_BEFORE.contains(after)
+        && !NO_BREAK_AFTER.contains(before)
+}
+
+fn is_wide(c: char) -> bool {
+    c.width() == Some(2)
+}
+
+#[cfg(test)]
+mod tests {
+    fn document(body: &str) -> String {
+        format!(
+            "<?xml version=\"1.0\"?><w:document xmlns:w=\"{}\"><w:body>{}</w:body></w:document>",
+            super::W,
+            body
+        )
+    }
+
+    #[test]
+    fn paragraph_and_character_styles_become_markdown() {
+        let xml = document(concat!(
+            r#"<w:p><w:pPr><w:pStyle w:val="HeadA"/></w:pPr><w:r><w:t>變數</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="Body"/></w:pPr>"#,
+            r#"<w:r><w:t xml:space="preserve">使用 </w:t></w:r>"#,
+            r#"<w:r><w:rPr><w:rStyle w:val="Literal"/></w:rPr><w:t>let</w:t></w:r>"#,
+            r#"<w:r><w:t xml:space="preserve"> 宣告</w:t></w:r>"#,
+            r#"<w:r><w:rPr><w:rStyle w:val="EmphasisItalic"/></w:rPr><w:t xml:space="preserve"> 不可變 </w:t></w:r>"#,
+            r#"</w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="Normal"/></w:pPr><w:r><w:t>略過</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr/><w:r><w:t>略過</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="BulletA"/></w:pPr><w:r><w:t>項目</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="Fancy"/></w:pPr></w:p>"#,
+        ));
+
+        assert_eq!(
+            super::document_to_markdown(&xml).unwrap(),
+            "## 變數\n\n使用 `let` 宣告 *不可變* \n\n* 項目\n\nUnmatched: Fancy\n      "
+        );
+    }
+
+    #[test]
+    fn code_paragraphs_ignore_character_styles() {
+        let xml = document(concat!(
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeA"/></w:pPr><w:r><w:t>fn main() {</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeB"/></w:pPr><w:r><w:rPr><w:rStyle w:val="Literal"/></w:rPr><w:t xml:space="preserve">    let x = 5;</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeC"/></w:pPr><w:r><w:t>}</w:t></w:r></w:p>"#,
+        ));
+
+        assert_eq!(
+            super::document_to_markdown(&xml).unwrap(),
+            "```\nfn main() {\n    let x = 5;\n}\n```\n\n"
+        );
+    }
+
+    #[test]
+    fn wingding_code_paragraphs_are_code_too() {
+        let xml = document(concat!(
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeAWingding"/></w:pPr><w:r><w:t>let x = 5;</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeBWingding"/></w:pPr><w:r><w:t>let y = x;</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="CodeCWingding"/></w:pPr><w:r><w:t>println!("{y}");</w:t></w:r></w:p>"#,
+            r#"<w:p><w:pPr><w:pStyle w:val="Body"/></w:pPr><w:r><w:t>內文</w:t></w:r></w:p>"#,
+        ));
+
+        assert_eq!(
+            super::document_to_markdown(&xml).unwrap(),
+            "```\nlet x = 5;\nlet y = x;\nprintln!(\"{y}\");\n```\n\n內文\n\n"
+        );
+    }
+
+    #[test]
+    fn wrap_breaks_at_spaces_and_between_cjk_characters() {
+        assert_eq!(
+            super::wrap("the quick brown fox jumps\n", 10),
+            "the quick\nbrown fox\njumps\n"
+        );
+        assert_eq!(super::wrap("一二三四五六七\n", 10), "一二三四五\n六七\n");
+        // Closing punctuation stays with the character before it
+        assert_eq!(super::wrap("一二三四，五六\n", 8), "一二三\n四，五六\n");
+        assert_eq!(
+            super::wrap("```\n一二三四五六七\n```\n", 10),
+            "```\n一二三四五六七\n```\n"
+        );
+    }
+}
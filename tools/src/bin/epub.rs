@@ -0,0 +1,678 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use pulldown_cmark::{
+    html, CodeBlockKind, CowStr, Event, Options, Parser, Tag,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let src_dir = Path::new(&args.arg_src_dir);
+    let summary = Summary::parse(&fs::read_to_string(&args.flag_summary)?);
+
+    let mut chapters = Vec::new();
+    for page in &summary.pages {
+        let path = src_dir.join(&page.file);
+        if !path.exists() {
+            eprintln!("Skipping {}, which doesn't exist", path.display());
+            continue;
+        }
+        let markdown = fs::read_to_string(&path)?;
+        chapters.push(Chapter {
+            id: format!("page-{}", chapters.len() + 1),
+            file_name: xhtml_file_name(&page.file),
+            title: page.title.clone(),
+            body: markdown_to_xhtml(&markdown),
+        });
+    }
+
+    let images = collect_images(Path::new(&args.flag_img_dir))?;
+    let modified = modified_timestamp();
+
+    let file = fs::File::create(&args.arg_output_file)?;
+    let mut zip = ZipWriter::new(file);
+    let stored =
+        FileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated =
+        FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // The `mimetype` file has to come first and be stored uncompressed
+    zip.start_file("mimetype", stored)?;
+    zip.write_all(b"application/epub+zip")?;
+    zip.start_file("META-INF/container.xml", deflated)?;
+    zip.write_all(CONTAINER_XML.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", deflated)?;
+    zip.write_all(
+        package_document(&summary.title, &modified, &chapters, &images)
+            .as_bytes(),
+    )?;
+    zip.start_file("OEBPS/nav.xhtml", deflated)?;
+    zip.write_all(nav_document(&summary, &chapters).as_bytes())?;
+    zip.start_file("OEBPS/book.css", deflated)?;
+    zip.write_all(STYLESHEET.as_bytes())?;
+    for chapter in &chapters {
+        zip.start_file(format!("OEBPS/{}", chapter.file_name), deflated)?;
+        zip.write_all(xhtml_page(&chapter.title, &chapter.body).as_bytes())?;
+    }
+    for image in &images {
+        zip.start_file(format!("OEBPS/img/{}", image.name), deflated)?;
+        zip.write_all(&fs::read(&image.path)?)?;
+    }
+    zip.finish()?;
+
+    println!(
+        "Wrote {} page(s) and {} image(s) to {}",
+        chapters.len(),
+        images.len(),
+        args.arg_output_file
+    );
+
+    Ok(())
+}
+
+const USAGE: &str = "
+epub
+Writes an EPUB 3 edition of the book from the cleaned Markdown in <src-dir>,
+one file per entry of `SUMMARY.md`, like `tools/epub.sh` prepares it.
+
+Usage:
+  epub [options] <src-dir> <output-file>
+  epub (-h | --help)
+
+Options:
+  -h --help         Show this screen.
+  --summary=<file>  The book's summary [default: src/SUMMARY.md].
+  --img-dir=<dir>   Images the chapters use [default: src/img].
+
+The `dcterms:modified` date comes from `SOURCE_DATE_EPOCH` when it's set, so
+the same sources always give the same file.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+    arg_output_file: String,
+    flag_summary: String,
+    flag_img_dir: String,
+}
+
+const LANGUAGE: &str = "zh-Hant";
+const IDENTIFIER: &str = "https://rust-lang.tw/book-tw/";
+
+// Entries of `SUMMARY.md` as the nav document shows them.
+#[derive(Debug, PartialEq)]
+struct NavNode {
+    title: String,
+    file: Option<String>,
+    children: Vec<NavNode>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Page {
+    title: String,
+    file: String,
+}
+
+#[derive(Debug)]
+struct Summary {
+    title: String,
+    // Every linked file in reading order
+    pages: Vec<Page>,
+    nav: Vec<NavNode>,
+}
+
+lazy_static! {
+    static ref BOOK_TITLE: Regex = Regex::new(r"^#\s+(.*)$").unwrap();
+    static ref PART_TITLE: Regex = Regex::new(r"^##+\s+(.*)$").unwrap();
+    static ref SUMMARY_LINK: Regex =
+        Regex::new(r"^(\s*)(-\s+)?\[(.*)\]\(([^)]+)\)").unwrap();
+}
+
+impl Summary {
+    // Parts become nav entries without a page, holding their chapters, and
+    // sections are nested under their chapter.
+    fn parse(summary: &str) -> Summary {
+        let mut title = String::new();
+        let mut pages = Vec::new();
+        let mut nav: Vec<NavNode> = Vec::new();
+        let mut in_part = false;
+
+        for line in summary.lines() {
+            if let Some(caps) = PART_TITLE.captures(line) {
+                nav.push(NavNode {
+                    title: caps[1].to_string(),
+                    file: None,
+                    children: Vec::new(),
+                });
+                in_part = true;
+                continue;
+            }
+            if let Some(caps) = BOOK_TITLE.captures(line) {
+                title = caps[1].to_string();
+                continue;
+            }
+            let caps = match SUMMARY_LINK.captures(line) {
+                Some(caps) => caps,
+                None => continue,
+            };
+            let node = NavNode {
+                title: caps[3].to_string(),
+                file: Some(caps[4].to_string()),
+                children: Vec::new(),
+            };
+            pages.push(Page {
+                title: caps[3].to_string(),
+                file: caps[4].to_string(),
+            });
+
+            let is_numbered = caps.get(2).is_some();
+            let is_section = is_numbered && !caps[1].is_empty();
+            let siblings = if !is_numbered || !in_part {
+                &mut nav
+            } else {
+                &mut nav.last_mut().expect("Parts should be in nav").children
+            };
+            match siblings.last_mut() {
+                Some(chapter) if is_section => chapter.children.push(node),
+                _ => siblings.push(node),
+            }
+        }
+
+        Summary { title, pages, nav }
+    }
+}
+
+struct Chapter {
+    id: String,
+    file_name: String,
+    title: String,
+    body: String,
+}
+
+struct Image {
+    // Relative to the image directory, with forward slashes
+    name: String,
+    path: std::path::PathBuf,
+}
+
+fn xhtml_file_name(markdown_file: &str) -> String {
+    Path::new(markdown_file)
+        .with_extension("xhtml")
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn collect_images(img_dir: &Path) -> Result<Vec<Image>, Box<dyn Error>> {
+    let mut images = Vec::new();
+    for entry in walkdir::WalkDir::new(img_dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    {
+        let entry = entry?;
+        if entry.file_type().is_file()
+            && media_type(&entry.path().to_string_lossy()).is_some()
+        {
+            let relative = entry.path().strip_prefix(img_dir)?;
+            images.push(Image {
+                name: relative
+                    .components()
+                    .map(|part| part.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/"),
+                path: entry.path().to_owned(),
+            });
+        }
+    }
+    Ok(images)
+}
+
+fn media_type(file: &str) -> Option<&'static str> {
+    match Path::new(file).extension()?.to_str()? {
+        "svg" => Some("image/svg+xml"),
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        _ => None,
+    }
+}
+
+// Ferris and its title for each code block annotation, the same as the
+// `ferris` preprocessor uses for the website.
+const FERRIS_TYPES: &[(&str, &str)] = &[
+    ("does_not_compile", "此程式碼無法編譯！"),
+    ("panics", "此程式碼會恐慌！"),
+    ("not_desired_behavior", "此程式碼沒有產生預期的行為。"),
+];
+
+fn markdown_to_xhtml(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let events = add_ferris(Parser::new_ext(markdown, options).collect());
+
+    let mut body = String::new();
+    html::push_html(&mut body, events.into_iter());
+    fix_up_html(&body)
+}
+
+// Puts Ferris before every annotated code block, and keeps only the language
+// of the info string, so the code gets a `language-rust` class.
+fn add_ferris(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    for (i, event) in events.iter().enumerate() {
+        let info = match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info,
+            _ => {
+                output.push(event.clone());
+                continue;
+            }
+        };
+        let attributes = info.split(',').map(str::trim).collect::<Vec<_>>();
+        let ferris = FERRIS_TYPES
+            .iter()
+            .find(|(attr, _)| attributes.contains(attr));
+        if let Some((attr, title)) = ferris {
+            let code = events[i + 1..]
+                .iter()
+                .take_while(|event| !matches!(event, Event::End(_)))
+                .filter_map(|event| match event {
+                    Event::Text(text) => Some(text.as_ref()),
+                    _ => None,
+                })
+                .collect::<String>();
+            // The website uses the small Ferris for code shorter than four
+            // lines
+            let size = if code.trim_end_matches('\n').lines().count() < 4 {
+                "small"
+            } else {
+                "large"
+            };
+            output.push(Event::Html(CowStr::from(format!(
+                "<div class=\"ferris-container\"><img src=\"img/ferris/{attr}.svg\" \
+                 alt=\"{title}\" title=\"{title}\" class=\"ferris ferris-{size}\" /></div>\n",
+                attr = attr,
+                title = title,
+                size = size
+            ))));
+        }
+        let language = attributes.first().copied().unwrap_or_default();
+        output.push(Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(
+            CowStr::from(language.to_string()),
+        ))));
+    }
+    output
+}
+
+lazy_static! {
+    static ref HTML_COMMENT: Regex = Regex::new(r"(?s)<!--.*?-->").unwrap();
+    static ref VOID_ELEMENT: Regex =
+        Regex::new(r"(?s)<(br|hr|img)\b([^>]*?)\s*/?>").unwrap();
+}
+
+// The chapters have raw HTML written for browsers, but EPUB pages are XML.
+// Comments go, since ours sometimes contain `--`, void elements are closed and
+// `&nbsp;` isn't defined in XHTML.
+fn fix_up_html(html: &str) -> String {
+    let html = HTML_COMMENT.replace_all(html, "");
+    let html = VOID_ELEMENT.replace_all(&html, "<$1$2 />");
+    html.replace("&nbsp;", "&#160;")
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Titles in `SUMMARY.md` can have inline code
+fn plain_title(title: &str) -> String {
+    escape_xml(&title.replace('`', ""))
+}
+
+fn xhtml_page(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" xml:lang="{lang}" lang="{lang}">
+<head>
+<meta charset="UTF-8" />
+<title>{title}</title>
+<link rel="stylesheet" type="text/css" href="book.css" />
+</head>
+<body>
+{body}</body>
+</html>
+"#,
+        lang = LANGUAGE,
+        title = plain_title(title),
+        body = body
+    )
+}
+
+const CONTAINER_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>
+"#;
+
+fn package_document(
+    title: &str,
+    modified: &str,
+    chapters: &[Chapter],
+    images: &[Image],
+) -> String {
+    let mut manifest = String::from(
+        "    <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         \x20   <item id=\"css\" href=\"book.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for chapter in chapters {
+        let properties = if chapter.body.contains("<svg") {
+            " properties=\"svg\""
+        } else {
+            ""
+        };
+        manifest.push_str(&format!(
+            "    <item id=\"{}\" href=\"{}\" media-type=\"application/xhtml+xml\"{}/>\n",
+            chapter.id,
+            escape_xml(&chapter.file_name),
+            properties
+        ));
+        spine.push_str(&format!("    <itemref idref=\"{}\"/>\n", chapter.id));
+    }
+    for (i, image) in images.iter().enumerate() {
+        manifest.push_str(&format!(
+            "    <item id=\"img-{}\" href=\"img/{}\" media-type=\"{}\"/>\n",
+            i + 1,
+            escape_xml(&image.name),
+            media_type(&image.name).expect("Images should have a media type")
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="book-id" xml:lang="{lang}">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:identifier id="book-id">{identifier}</dc:identifier>
+    <dc:title>{title}</dc:title>
+    <dc:language>{lang}</dc:language>
+    <dc:creator>Steve Klabnik</dc:creator>
+    <dc:creator>Carol Nichols</dc:creator>
+    <dc:contributor>Rust Taiwan</dc:contributor>
+    <meta property="dcterms:modified">{modified}</meta>
+  </metadata>
+  <manifest>
+{manifest}  </manifest>
+  <spine>
+{spine}  </spine>
+</package>
+"#,
+        lang = LANGUAGE,
+        identifier = IDENTIFIER,
+        title = plain_title(title),
+        modified = modified,
+        manifest = manifest,
+        spine = spine
+    )
+}
+
+fn nav_document(summary: &Summary, chapters: &[Chapter]) -> String {
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n{}</nav>\n",
+        plain_title(&summary.title),
+        nav_list(&summary.nav, chapters)
+    );
+    xhtml_page(&summary.title, &body)
+}
+
+// Entries whose page is missing are still shown, just not linked.
+fn nav_list(nodes: &[NavNode], chapters: &[Chapter]) -> String {
+    let mut list = String::from("<ol>\n");
+    for node in nodes {
+        let file_name = node.file.as_deref().map(xhtml_file_name);
+        let label = match file_name {
+            Some(file_name)
+                if chapters
+                    .iter()
+                    .any(|chapter| chapter.file_name == file_name) =>
+            {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_xml(&file_name),
+                    plain_title(&node.title)
+                )
+            }
+            _ => format!("<span>{}</span>", plain_title(&node.title)),
+        };
+        list.push_str(&format!("<li>{}", label));
+        if !node.children.is_empty() {
+            list.push('\n');
+            list.push_str(&nav_list(&node.children, chapters));
+        }
+        list.push_str("</li>\n");
+    }
+    list.push_str("</ol>\n");
+    list
+}
+
+// `dcterms:modified` in the `CCYY-MM-DDThh:mm:ssZ` form EPUB requires
+fn modified_timestamp() -> String {
+    let seconds = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs())
+        });
+    format_timestamp(seconds)
+}
+
+fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / 86_400) as i64;
+    let time = seconds % 86_400;
+
+    // Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+const STYLESHEET: &str = r#"body {
+  font-family: serif;
+  line-height: 1.6;
+}
+
+pre {
+  white-space: pre-wrap;
+  font-size: 0.85em;
+  background: #f6f7f6;
+  padding: 0.5em;
+}
+
+code {
+  font-family: monospace;
+}
+
+table {
+  border-collapse: collapse;
+}
+
+td, th {
+  border: 1px solid #ccc;
+  padding: 0.2em 0.5em;
+}
+
+img {
+  max-width: 100%;
+}
+
+.filename {
+  display: block;
+  font-size: 0.85em;
+  font-weight: bold;
+}
+
+.caption,
+figure.listing figcaption {
+  display: block;
+  font-size: 0.85em;
+  font-style: italic;
+  text-align: center;
+}
+
+figure.listing {
+  margin: 0;
+}
+
+.ferris-container {
+  text-align: right;
+}
+
+.ferris {
+  vertical-align: top;
+  height: auto;
+}
+
+.ferris-large {
+  width: 4.5em;
+}
+
+.ferris-small {
+  width: 2.3em;
+}
+
+.ferris-explain {
+  width: 100px;
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::{NavNode, Summary};
+
+    fn node(
+        title: &str,
+        file: Option<&str>,
+        children: Vec<NavNode>,
+    ) -> NavNode {
+        NavNode {
+            title: title.to_string(),
+            file: file.map(|file| file.to_string()),
+            children,
+        }
+    }
+
+    #[test]
+    fn summary_nests_chapters_in_parts_and_sections_in_chapters() {
+        let summary = Summary::parse(
+            "# Rust 程式設計語言
+
+[前言](foreword.md)
+
+## 開始入門
+
+- [開始入門](ch01-00-getting-started.md)
+    - [安裝教學](ch01-01-installation.md)
+- [設計猜謎遊戲程式](ch02-00-guessing-game-tutorial.md)
+",
+        );
+
+        assert_eq!(summary.title, "Rust 程式設計語言");
+        assert_eq!(summary.pages.len(), 4);
+        assert_eq!(
+            summary.nav,
+            vec![
+                node("前言", Some("foreword.md"), Vec::new()),
+                node(
+                    "開始入門",
+                    None,
+                    vec![
+                        node(
+                            "開始入門",
+                            Some("ch01-00-getting-started.md"),
+                            vec![node(
+                                "安裝教學",
+                                Some("ch01-01-installation.md"),
+                                Vec::new()
+                            )]
+                        ),
+                        node(
+                            "設計猜謎遊戲程式",
+                            Some("ch02-00-guessing-game-tutorial.md"),
+                            Vec::new()
+                        ),
+                    ]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_to_xhtml_adds_ferris_by_code_length() {
+        let xhtml = super::markdown_to_xhtml(
+            "```rust,ignore,does_not_compile\nlet x = 5;\nx = 6;\n```\n\n```rust,panics\n1\n2\n3\n4\n```\n",
+        );
+
+        assert!(xhtml.contains(
+            "<img src=\"img/ferris/does_not_compile.svg\" alt=\"此程式碼無法編譯！\" \
+             title=\"此程式碼無法編譯！\" class=\"ferris ferris-small\" />"
+        ));
+        assert!(xhtml.contains("class=\"ferris ferris-large\""));
+        assert!(xhtml.contains("<code class=\"language-rust\">"));
+    }
+
+    #[test]
+    fn fix_up_html_makes_raw_html_well_formed_xml() {
+        assert_eq!(
+            super::fix_up_html(
+                "<!-- a -- b --><img src=\"a.svg\"\nclass=\"center\"><br>&nbsp;"
+            ),
+            "<img src=\"a.svg\"\nclass=\"center\" /><br />&#160;"
+        );
+    }
+
+    #[test]
+    fn format_timestamp_formats_utc_dates() {
+        assert_eq!(super::format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            super::format_timestamp(1_667_433_600 + 3_723),
+            "2022-11-03T01:02:03Z"
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use docopt::Docopt;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use serde::Deserialize;
+use serde_json::Value;
+use std::error::Error;
+use std::io::{self, Read};
+use std::process;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    if args.cmd_supports {
+        let supported = RENDERERS.contains(&args.arg_renderer.as_str());
+        process::exit(if supported { 0 } else { 1 });
+    }
+
+    // mdbook passes `[context, book]` and expects the book back
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let (_context, mut book): (Value, Value) = serde_json::from_str(&input)?;
+    if let Some(sections) = book.get_mut("sections") {
+        add_ferris_to_items(sections);
+    }
+    serde_json::to_writer(io::stdout(), &book)?;
+
+    Ok(())
+}
+
+const USAGE: &str = "
+ferris
+An mdbook preprocessor that puts Ferris next to the code blocks annotated
+with `does_not_compile`, `panics` or `not_desired_behavior`, so the HTML has
+it without JavaScript.
+
+Usage:
+  ferris
+  ferris supports <renderer>
+  ferris (-h | --help)
+
+Options:
+  -h --help  Show this screen.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    cmd_supports: bool,
+    arg_renderer: String,
+}
+
+// The Markdown renderer isn't one of them, `nostarch.sh` wants the chapters
+// without HTML for Ferris
+const RENDERERS: &[&str] = &["html", "epub"];
+
+// Ferris and its title for each code block annotation
+const FERRIS_TYPES: &[(&str, &str)] = &[
+    ("does_not_compile", "此程式碼無法編譯！"),
+    ("panics", "此程式碼會恐慌！"),
+    ("not_desired_behavior", "此程式碼沒有產生預期的行為。"),
+];
+
+fn add_ferris_to_items(items: &mut Value) {
+    for item in items.as_array_mut().into_iter().flatten() {
+        if let Some(chapter) = item.get_mut("Chapter") {
+            if let Some(Value::String(content)) = chapter.get_mut("content") {
+                *content = add_ferris(content);
+            }
+            if let Some(sub_items) = chapter.get_mut("sub_items") {
+                add_ferris_to_items(sub_items);
+            }
+        }
+    }
+}
+
+// Writes every annotated code block as the HTML pulldown-cmark would make of
+// it, with Ferris in the `<pre>` where `ferris.js` put it. That's an HTML
+// block of its own, so every line gets the list or block quote prefix of the
+// fence.
+fn add_ferris(markdown: &str) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut replacements = Vec::new();
+    let mut current = None;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter()
+    {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+                let attributes =
+                    info.split(',').map(str::trim).collect::<Vec<_>>();
+                current = FERRIS_TYPES
+                    .iter()
+                    .find(|(attr, _)| attributes.contains(attr))
+                    .map(|ferris| {
+                        (range, info.to_string(), ferris, String::new())
+                    });
+            }
+            Event::Text(text) => {
+                if let Some((_, _, _, code)) = &mut current {
+                    code.push_str(&text);
+                }
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((range, info, (attr, title), code)) = current.take()
+                {
+                    let is_rust = info.split(',').next() == Some("rust");
+                    let size = if visible_lines(&code, is_rust) < 4 {
+                        "small"
+                    } else {
+                        "large"
+                    };
+                    let html = code_block_html(
+                        &info,
+                        &code,
+                        &ferris_html(attr, title, size, is_playground(&info)),
+                    );
+                    replacements.push((range, html));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for (range, html) in replacements {
+        let line_start =
+            markdown[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let end = if markdown[..range.end].ends_with('\n') {
+            range.end - 1
+        } else {
+            range.end
+        };
+        let prefix = &markdown[line_start..range.start];
+        // Markers of list items are only on their first line
+        let continuation = prefix
+            .chars()
+            .map(|c| if c == '>' { '>' } else { ' ' })
+            .collect::<String>();
+        output.push_str(&markdown[position..line_start]);
+        for (i, line) in html.split('\n').enumerate() {
+            if i == 0 {
+                output.push_str(prefix);
+            } else {
+                output.push('\n');
+                output.push_str(if line.is_empty() {
+                    continuation.trim_end()
+                } else {
+                    &continuation
+                });
+            }
+            output.push_str(line);
+        }
+        position = end;
+    }
+    output.push_str(&markdown[position..]);
+    output
+}
+
+// Like `pulldown-cmark` renders a fenced code block, with `ferris` first
+fn code_block_html(info: &str, code: &str, ferris: &str) -> String {
+    let language = info.split(' ').next().unwrap_or_default();
+    format!(
+        "<pre>{}<code class=\"language-{}\">{}</code></pre>",
+        ferris,
+        escape_html(language),
+        escape_html(code)
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Whether mdbook puts the code in a `<pre class="playground">` of its own,
+// which gets its own buttons
+fn is_playground(info: &str) -> bool {
+    info.split(',').next() == Some("rust")
+        && !["ignore", "noplayground", "noplaypen"]
+            .iter()
+            .any(|class| info.contains(class))
+        || info.contains("mdbook-runnable")
+}
+
+// The lines the reader sees. Like `ferris.js` counted them, that leaves out
+// the lines mdbook hides in Rust code.
+fn visible_lines(code: &str, is_rust: bool) -> usize {
+    code.lines()
+        .filter(|line| {
+            let line = line.trim_start();
+            !is_rust || !(line.starts_with("# ") || line == "#")
+        })
+        .count()
+}
+
+// `ferris.js` put small Ferris with the buttons of the code block, which
+// mdbook's `book.js` adds to the `.buttons` it finds. Playgrounds get buttons
+// of their own, so there Ferris gets a container like large ones.
+fn ferris_html(
+    attr: &str,
+    title: &str,
+    size: &str,
+    is_playground: bool,
+) -> String {
+    let class = if size == "small" && !is_playground {
+        "buttons"
+    } else {
+        "ferris-container"
+    };
+    format!(
+        "<div class=\"{class}\"><a href=\"ch00-00-introduction.html#ferris\" target=\"_blank\">\
+         <img src=\"img/ferris/{attr}.svg\" title=\"{title}\" class=\"ferris ferris-{size}\"></a></div>",
+        class = class,
+        attr = attr,
+        title = title,
+        size = size
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{html, Parser};
+
+    fn render(markdown: &str) -> String {
+        let mut output = String::new();
+        html::push_html(&mut output, Parser::new(markdown));
+        output
+    }
+
+    #[test]
+    fn ferris_goes_in_the_pre_of_annotated_code_blocks() {
+        let markdown = "文字\n\n```rust,ignore,does_not_compile\nfn main() {\n    let x = 5;\n\n    x = 6;\n}\n```\n\n```rust\nfn main() {}\n```\n";
+        let ferris = super::ferris_html(
+            "does_not_compile",
+            "此程式碼無法編譯！",
+            "large",
+            false,
+        );
+
+        assert_eq!(
+            render(&super::add_ferris(markdown)),
+            render(markdown).replacen("<pre>", &format!("<pre>{}", ferris), 1)
+        );
+    }
+
+    #[test]
+    fn ferris_stays_in_list_items_and_block_quotes() {
+        let markdown =
+            "1. 清單\n\n   ```text,not_desired_behavior\n   a < b\n\n   c\n   ```\n\n> ```rust,ignore,panics\n> panic!(\"&\");\n> ```\n後面\n";
+        let not_desired = super::ferris_html(
+            "not_desired_behavior",
+            "此程式碼沒有產生預期的行為。",
+            "small",
+            false,
+        );
+        let panics =
+            super::ferris_html("panics", "此程式碼會恐慌！", "small", false);
+
+        assert_eq!(
+            render(&super::add_ferris(markdown)),
+            render(markdown)
+                .replacen("<pre>", &format!("<pre>{}", not_desired), 1)
+                .replacen("<pre><code", &format!("<pre>{}<code", panics), 1)
+        );
+    }
+
+    #[test]
+    fn small_ferris_goes_with_the_buttons_except_in_playgrounds() {
+        let markdown = "```rust,panics\n# fn main() {\nlet v = vec![1];\nv[99];\n# }\n```\n";
+
+        assert!(super::add_ferris(markdown).starts_with(&format!(
+            "<pre>{}<code",
+            super::ferris_html("panics", "此程式碼會恐慌！", "small", true)
+        )));
+        assert!(super::ferris_html("panics", "", "small", false)
+            .starts_with("<div class=\"buttons\">"));
+        assert!(super::ferris_html("panics", "", "small", true)
+            .starts_with("<div class=\"ferris-container\">"));
+    }
+}
@@ -0,0 +1,183 @@
+use docopt::Docopt;
+use serde::Deserialize;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::thread;
+
+#[path = "../rustfmt_ignore.rs"]
+mod rustfmt_ignore;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let listings_dir = Path::new(&args.flag_listings_dir);
+    let jobs = match args.flag_jobs {
+        Some(jobs) => jobs.max(1),
+        None => thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+
+    let mut to_format = Vec::new();
+    for crate_dir in crate_dirs(listings_dir)? {
+        match rustfmt_ignore::reason(listings_dir, &crate_dir)? {
+            Some(reason) => {
+                println!("Skipping {}: {}", crate_dir.display(), reason)
+            }
+            None => to_format.push(crate_dir),
+        }
+    }
+
+    let results = run_in_parallel(&to_format, jobs, |crate_dir| {
+        cargo_fmt(crate_dir, args.flag_check)
+    });
+
+    let mut found_errs = false;
+    for (crate_dir, result) in to_format.iter().zip(results) {
+        match result {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => {
+                found_errs = true;
+                if args.flag_check {
+                    eprintln!("Unformatted: {}", crate_dir.display());
+                } else {
+                    eprintln!("Failed to format {}:", crate_dir.display());
+                }
+                eprint!("{}", String::from_utf8_lossy(&output.stdout));
+                eprint!("{}", String::from_utf8_lossy(&output.stderr));
+            }
+            Err(err) => {
+                found_errs = true;
+                eprintln!(
+                    "Unable to run `cargo fmt` in {}: {}",
+                    crate_dir.display(),
+                    err
+                );
+            }
+        }
+    }
+
+    if found_errs {
+        std::process::exit(1)
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "
+format_listings
+Runs `cargo fmt` on every listing, skipping listings with a `rustfmt-ignore`
+file.
+
+Usage:
+  format_listings [options]
+  format_listings (-h | --help)
+
+Options:
+  -h --help              Show this screen.
+  --check                Only report listings that aren't formatted.
+  --jobs=<n>             How many listings to format at once, defaults to
+                         the number of CPUs.
+  --listings-dir=<dir>   Where the listings are [default: listings].
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    flag_check: bool,
+    flag_jobs: Option<usize>,
+    flag_listings_dir: String,
+}
+
+// Every directory with a `Cargo.toml` is a crate to format, except for
+// members of a workspace: `cargo fmt --all` in the workspace root already
+// takes care of them.
+fn crate_dirs(listings_dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let walker = walkdir::WalkDir::new(listings_dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == "Cargo.toml" {
+            let dir = entry
+                .path()
+                .parent()
+                .expect("Manifest should've been in a directory");
+            dirs.push(dir.to_owned());
+        }
+    }
+    let workspace_members: Vec<PathBuf> = dirs
+        .iter()
+        .filter(|dir| {
+            dirs.iter()
+                .any(|other| other != *dir && dir.starts_with(other))
+        })
+        .cloned()
+        .collect();
+    dirs.retain(|dir| !workspace_members.contains(dir));
+    Ok(dirs)
+}
+
+fn cargo_fmt(crate_dir: &Path, check: bool) -> std::io::Result<Output> {
+    let mut command = Command::new("cargo");
+    command.args(["fmt", "--all"]).current_dir(crate_dir);
+    if check {
+        command.args(["--", "--check"]);
+    }
+    command.output()
+}
+
+// Runs `f` on every item using `jobs` threads and returns the results in the
+// same order as the items.
+fn run_in_parallel<T, R, F>(items: &[T], jobs: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = Mutex::new(items.iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let item = next.lock().unwrap().next();
+                match item {
+                    Some((index, item)) => {
+                        let result = f(item);
+                        results.lock().unwrap().push((index, result));
+                    }
+                    None => break,
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn run_in_parallel_keeps_the_order_of_the_items() {
+        let items: Vec<u32> = (0..100).collect();
+
+        let results = super::run_in_parallel(&items, 8, |item| item * 2);
+
+        assert_eq!(results, (0..100).map(|item| item * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn run_in_parallel_handles_more_jobs_than_items() {
+        let items = vec!["listing-04-01", "listing-04-02"];
+
+        let results = super::run_in_parallel(&items, 16, |item| item.len());
+
+        assert_eq!(results, vec![13, 13]);
+    }
+}
@@ -0,0 +1,295 @@
+use docopt::Docopt;
+use pulldown_cmark::{Event, Options, Parser, Tag};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    if args.cmd_supports {
+        let supported = RENDERERS.contains(&args.arg_renderer.as_str());
+        process::exit(if supported { 0 } else { 1 });
+    }
+
+    // mdbook passes `[context, book]` and expects the book back
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let (context, mut book): (Value, Value) = serde_json::from_str(&input)?;
+
+    let root = context["root"].as_str().unwrap_or(".");
+    let src = context["config"]["book"]["src"].as_str().unwrap_or("src");
+    let terminology = context
+        .pointer("/config/preprocessor/glossary/terminology")
+        .and_then(Value::as_str)
+        .unwrap_or(TERMINOLOGY);
+    let path = Path::new(root).join(src).join(terminology);
+    let glossary = Glossary::parse(&fs::read_to_string(&path)?);
+
+    if let Some(sections) = book.get_mut("sections") {
+        mark_items(sections, &glossary, terminology);
+    }
+    serde_json::to_writer(io::stdout(), &book)?;
+
+    Ok(())
+}
+
+const USAGE: &str = "
+glossary
+An mdbook preprocessor that marks the first time each term of the terminology
+appendix shows up in a chapter with an `<abbr>` of the English term, counting
+the translations of an English term as one. Terms in code, headings and links
+are left alone.
+
+Usage:
+  glossary
+  glossary supports <renderer>
+  glossary (-h | --help)
+
+Options:
+  -h --help  Show this screen.
+
+The appendix is `appendix-08-terminology.md` in the book's source, or the
+`terminology` option of `[preprocessor.glossary]` in `book.toml`.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    cmd_supports: bool,
+    arg_renderer: String,
+}
+
+const TERMINOLOGY: &str = "appendix-08-terminology.md";
+
+// Print and the Markdown for No Starch get the terms as they are
+const RENDERERS: &[&str] = &["html", "epub"];
+
+// The translated terms of the terminology table, by their first character
+// and longest first, with the English term each translates
+struct Glossary {
+    terms: BTreeMap<char, Vec<(String, String)>>,
+}
+
+impl Glossary {
+    // Rows are `English | 中文、中文 | Note`. Terms of a single character are
+    // left out, they're part of too many other words.
+    fn parse(markdown: &str) -> Glossary {
+        let mut terms: BTreeMap<char, Vec<(String, String)>> = BTreeMap::new();
+        let rows = markdown
+            .lines()
+            .skip_while(|line| !line.starts_with("---"))
+            .skip(1)
+            .take_while(|line| line.contains('|'));
+        for row in rows {
+            let columns = row.split('|').map(str::trim).collect::<Vec<_>>();
+            let (english, translations) = match columns.as_slice() {
+                [english, translations, ..] => (english, translations),
+                _ => continue,
+            };
+            for term in translations.split('、').map(str::trim) {
+                let first = match term.chars().next() {
+                    Some(first) if term.chars().count() > 1 => first,
+                    _ => continue,
+                };
+                terms
+                    .entry(first)
+                    .or_default()
+                    .push((term.to_string(), english.to_string()));
+            }
+        }
+        for candidates in terms.values_mut() {
+            candidates.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+        }
+        Glossary { terms }
+    }
+
+    // The longest term `text` starts with
+    fn longest_match(&self, text: &str) -> Option<&(String, String)> {
+        let first = text.chars().next()?;
+        self.terms
+            .get(&first)?
+            .iter()
+            .find(|(term, _)| text.starts_with(term.as_str()))
+    }
+}
+
+fn mark_items(items: &mut Value, glossary: &Glossary, terminology: &str) {
+    for item in items.as_array_mut().into_iter().flatten() {
+        if let Some(chapter) = item.get_mut("Chapter") {
+            let is_terminology = chapter["path"].as_str() == Some(terminology);
+            if let Some(Value::String(content)) = chapter.get_mut("content") {
+                if !is_terminology {
+                    *content = mark_terms(content, glossary);
+                }
+            }
+            if let Some(sub_items) = chapter.get_mut("sub_items") {
+                mark_items(sub_items, glossary, terminology);
+            }
+        }
+    }
+}
+
+fn mark_terms(markdown: &str, glossary: &Glossary) -> String {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_FOOTNOTES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    options.insert(Options::ENABLE_TASKLISTS);
+
+    let mut marks = Vec::new();
+    let mut marked = HashSet::new();
+    let mut skipped = 0;
+    let mut in_html_link = false;
+    let mut in_html_code = false;
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter()
+    {
+        match event {
+            Event::Start(
+                Tag::Heading(..)
+                | Tag::Link(..)
+                | Tag::Image(..)
+                | Tag::CodeBlock(_),
+            ) => skipped += 1,
+            Event::End(
+                Tag::Heading(..)
+                | Tag::Link(..)
+                | Tag::Image(..)
+                | Tag::CodeBlock(_),
+            ) => skipped -= 1,
+            Event::Html(html) => {
+                if html.starts_with("<a ") {
+                    in_html_link = !html.contains("</a>");
+                } else if html.starts_with("</a>") {
+                    in_html_link = false;
+                } else if html.starts_with("<code") {
+                    in_html_code = !html.contains("</code>");
+                } else if html.starts_with("</code>") {
+                    in_html_code = false;
+                }
+            }
+            // Text with escapes or entities isn't the same in the Markdown,
+            // so it's left alone
+            Event::Text(text)
+                if skipped == 0
+                    && !in_html_link
+                    && !in_html_code
+                    && markdown[range.clone()] == *text =>
+            {
+                let mut offset = 0;
+                while offset < text.len() {
+                    let rest = &text[offset..];
+                    match glossary.longest_match(rest) {
+                        Some((term, english)) => {
+                            // Synonyms count as the same term
+                            if marked.insert(english.clone()) {
+                                marks.push((
+                                    range.start + offset,
+                                    term,
+                                    english,
+                                ));
+                            }
+                            offset += term.len();
+                        }
+                        None => {
+                            offset +=
+                                rest.chars().next().map_or(1, char::len_utf8);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut output = String::with_capacity(markdown.len());
+    let mut position = 0;
+    for (start, term, english) in marks {
+        output.push_str(&markdown[position..start]);
+        output.push_str(&format!(
+            "<abbr title=\"{}\">{}</abbr>",
+            escape_attribute(english),
+            term
+        ));
+        position = start + term.len();
+    }
+    output.push_str(&markdown[position..]);
+    output
+}
+
+fn escape_attribute(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glossary;
+
+    const TERMINOLOGY: &str = "# 中英術語對照表
+
+English 英文           | Traditional Chinese 正體中文  | Note 備註
+------------           |-----------------------------  |----------
+borrowing              | 借用                          |
+borrow checker         | 借用檢查器                    |
+lifetimes              | 生命週期                      |
+reference              | 參照、參考                    | 參考：[維基百科][reference]
+values                 | 值、數值                      |
+
+[reference]: https://zh.wikipedia.org/
+";
+
+    #[test]
+    fn glossary_has_every_translation_of_a_term() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+
+        assert_eq!(
+            glossary.longest_match("參考的規則"),
+            Some(&("參考".to_string(), "reference".to_string()))
+        );
+        assert_eq!(
+            glossary.longest_match("借用檢查器會"),
+            Some(&("借用檢查器".to_string(), "borrow checker".to_string()))
+        );
+        assert_eq!(glossary.longest_match("值"), None);
+    }
+
+    #[test]
+    fn only_the_first_occurrence_is_marked() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+
+        assert_eq!(
+            super::mark_terms("借用檢查器會檢查借用，借用檢查器也會。\n", &glossary),
+            "<abbr title=\"borrow checker\">借用檢查器</abbr>會檢查<abbr title=\"borrowing\">借用</abbr>，借用檢查器也會。\n"
+        );
+    }
+
+    #[test]
+    fn synonyms_are_marked_once() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+
+        assert_eq!(
+            super::mark_terms("參照是一種參考。\n", &glossary),
+            "<abbr title=\"reference\">參照</abbr>是一種參考。\n"
+        );
+    }
+
+    #[test]
+    fn code_headings_and_links_are_skipped() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+        let markdown = "## 生命週期\n\n`借用` 和[參考](ch04.md)與<a href=\"#\">數值</a>和<code>借用</code>：\n\n```rust\n// 生命週期\n```\n\n    // 借用檢查器\n\n生命週期\n";
+
+        assert_eq!(
+            super::mark_terms(markdown, &glossary),
+            "## 生命週期\n\n`借用` 和[參考](ch04.md)與<a href=\"#\">數值</a>和<code>借用</code>：\n\n```rust\n// 生命週期\n```\n\n    // 借用檢查器\n\n<abbr title=\"lifetimes\">生命週期</abbr>\n"
+        );
+    }
+}
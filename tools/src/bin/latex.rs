@@ -0,0 +1,763 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use pulldown_cmark::{
+    Alignment, CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag,
+};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let src_dir = Path::new(&args.arg_src_dir);
+    let out_dir = Path::new(&args.arg_output_dir);
+    fs::create_dir_all(out_dir)?;
+
+    let summary = fs::read_to_string(&args.flag_summary)?;
+    let mut files = Vec::new();
+    for (file, is_numbered) in summary_files(&summary) {
+        let path = src_dir.join(&file);
+        if !path.exists() {
+            eprintln!("Skipping {}, which doesn't exist", path.display());
+            continue;
+        }
+        let markdown = fs::read_to_string(&path)?;
+        let blocks = parse_blocks(&markdown, &args.flag_book_url);
+        files.push((file, is_numbered, blocks));
+    }
+
+    // Mentions of listings can only point at listings that have a caption
+    let labels = files
+        .iter()
+        .flat_map(|(_, _, blocks)| blocks)
+        .filter_map(|block| match block {
+            Block::Code {
+                caption: Some(caption),
+                ..
+            } => Some(caption.number.clone()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+
+    let mut inputs = Vec::new();
+    for (file, is_numbered, blocks) in &files {
+        let name = Path::new(file)
+            .file_stem()
+            .expect("Chapters should have a file name")
+            .to_string_lossy()
+            .into_owned();
+        let tex = render_blocks(blocks, *is_numbered, &labels);
+        fs::write(out_dir.join(format!("{}.tex", name)), tex)?;
+        inputs.push((name, *is_numbered));
+    }
+    fs::write(
+        out_dir.join("book.tex"),
+        book_document(&summary_title(&summary), &inputs),
+    )?;
+
+    let img_dir = Path::new(&args.flag_img_dir);
+    for entry in walkdir::WalkDir::new(img_dir) {
+        let entry = entry?;
+        let target = out_dir
+            .join("img")
+            .join(entry.path().strip_prefix(img_dir)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    println!(
+        "Wrote {} chapter file(s) with {} listing(s) to {}",
+        inputs.len(),
+        labels.len(),
+        out_dir.join("book.tex").display()
+    );
+
+    Ok(())
+}
+
+const USAGE: &str = "
+latex
+Turns the Markdown in <src-dir>, one file per entry of `SUMMARY.md` like
+`tools/latex.sh` prepares it, into LaTeX for XeLaTeX and xeCJK. <output-dir>
+gets `book.tex`, a `.tex` file per chapter file and the images.
+
+Usage:
+  latex [options] <src-dir> <output-dir>
+  latex (-h | --help)
+
+Options:
+  -h --help          Show this screen.
+  --summary=<file>   The book's summary [default: src/SUMMARY.md].
+  --img-dir=<dir>    Images the chapters use [default: src/img].
+  --book-url=<url>   Where relative links point to in footnotes
+                     [default: https://rust-lang.tw/book-tw/].
+
+graphicx can't read SVG, so convert the SVG images in <output-dir>/img to PDF
+files next to them, for example with `rsvg-convert -f pdf`, before running
+`xelatex book.tex`.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+    arg_output_dir: String,
+    flag_summary: String,
+    flag_img_dir: String,
+    flag_book_url: String,
+}
+
+lazy_static! {
+    static ref SUMMARY_LINK: Regex =
+        Regex::new(r"^\s*(-\s+)?\[.*\]\(([^)]+)\)").unwrap();
+    static ref SUMMARY_TITLE: Regex = Regex::new(r"^#\s+(.*)$").unwrap();
+    static ref CHAPTER_FILE: Regex = Regex::new(r"^ch(\d\d)-").unwrap();
+}
+
+// Every file of `SUMMARY.md` in order, and whether it belongs to a numbered
+// chapter. The front matter, the introduction and the appendix aren't
+// numbered, so their headings don't take chapter numbers away from the
+// listings.
+fn summary_files(summary: &str) -> Vec<(String, bool)> {
+    summary
+        .lines()
+        .filter_map(|line| SUMMARY_LINK.captures(line))
+        .map(|caps| {
+            let file = caps[2].to_string();
+            let is_numbered = CHAPTER_FILE
+                .captures(&file)
+                .map_or(false, |chapter| &chapter[1] != "00");
+            (file, is_numbered)
+        })
+        .collect()
+}
+
+fn summary_title(summary: &str) -> String {
+    summary
+        .lines()
+        .find_map(|line| SUMMARY_TITLE.captures(line))
+        .map_or_else(String::new, |caps| caps[1].to_string())
+}
+
+#[derive(Debug, PartialEq)]
+enum Block {
+    // LaTeX that's ready, apart from references to listings
+    Text(String),
+    Code {
+        language: String,
+        code: String,
+        // As it is in the book, escaped when rendering
+        filename: Option<String>,
+        caption: Option<Caption>,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+struct Caption {
+    // Like `7-21`
+    number: String,
+    // LaTeX
+    text: String,
+}
+
+lazy_static! {
+    static ref CAPTION_NUMBER: Regex =
+        Regex::new(r"^範例\s*(\d+-\d+)\s*[：:]\s*").unwrap();
+    static ref FILENAME_PREFIX: Regex =
+        Regex::new(r"^檔案名稱[：:]\s*").unwrap();
+    static ref FIGURE_FILENAME: Regex =
+        Regex::new(r#"<span class="filename">(.*)</span>"#).unwrap();
+    static ref IMG_SRC: Regex =
+        Regex::new(r#"<img\b[^>]*\bsrc="([^"]+)""#).unwrap();
+}
+
+// What a paragraph turns out to be from the `<span>` it starts with
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParagraphKind {
+    Prose,
+    Filename,
+    Caption,
+}
+
+fn parse_blocks(markdown: &str, book_url: &str) -> Vec<Block> {
+    let mut options = Options::empty();
+    options.insert(Options::ENABLE_TABLES);
+    options.insert(Options::ENABLE_STRIKETHROUGH);
+    let events = Parser::new_ext(markdown, options).collect::<Vec<_>>();
+
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut paragraph = ParagraphKind::Prose;
+    let mut special = String::new();
+    let mut filename: Option<String> = None;
+    let mut code: Option<(String, String)> = None;
+    let mut links: Vec<String> = Vec::new();
+    let mut lists: Vec<bool> = Vec::new();
+    let mut cell = 0;
+    let mut in_heading = false;
+    let mut in_figcaption = false;
+
+    for (i, event) in events.iter().enumerate() {
+        // Filename and caption paragraphs are collected on their own
+        let out = if paragraph == ParagraphKind::Prose {
+            &mut text
+        } else {
+            &mut special
+        };
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                paragraph = match events.get(i + 1) {
+                    Some(Event::Html(html))
+                        if html.contains("class=\"filename\"") =>
+                    {
+                        ParagraphKind::Filename
+                    }
+                    Some(Event::Html(html))
+                        if html.contains("class=\"caption\"") =>
+                    {
+                        ParagraphKind::Caption
+                    }
+                    _ if in_figcaption => ParagraphKind::Caption,
+                    _ => ParagraphKind::Prose,
+                };
+            }
+            Event::End(Tag::Paragraph) => {
+                let kind = paragraph;
+                paragraph = ParagraphKind::Prose;
+                let content = std::mem::take(&mut special);
+                match kind {
+                    ParagraphKind::Prose => text.push_str("\n\n"),
+                    ParagraphKind::Filename => {
+                        filename = Some(
+                            FILENAME_PREFIX
+                                .replace(content.trim(), "")
+                                .into_owned(),
+                        );
+                    }
+                    ParagraphKind::Caption => {
+                        let caption = CAPTION_NUMBER
+                            .captures(content.trim())
+                            .map(|caps| Caption {
+                                number: caps[1].to_string(),
+                                text: content.trim()[caps[0].len()..]
+                                    .to_string(),
+                            });
+                        match (blocks.last_mut(), caption) {
+                            (
+                                Some(Block::Code {
+                                    caption: caption @ None,
+                                    ..
+                                }),
+                                Some(new_caption),
+                            ) if text.is_empty() => {
+                                *caption = Some(new_caption)
+                            }
+                            _ => {
+                                text.push_str(&format!(
+                                    "\\begin{{center}}\\small {}\\end{{center}}\n\n",
+                                    content.trim()
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            Event::Start(Tag::Heading(level, _, _)) => {
+                let command = match level {
+                    HeadingLevel::H1 => "chapter",
+                    HeadingLevel::H2 => "section",
+                    HeadingLevel::H3 => "subsection",
+                    HeadingLevel::H4 => "subsubsection",
+                    _ => "paragraph",
+                };
+                // Filled in when rendering, depending on the chapter
+                out.push_str(&format!("\\{}NUMBERING{{", command));
+                in_heading = true;
+            }
+            Event::End(Tag::Heading(..)) => {
+                out.push_str("}\n\n");
+                in_heading = false;
+            }
+            Event::Start(Tag::BlockQuote) => out.push_str("\\begin{quote}\n"),
+            Event::End(Tag::BlockQuote) => out.push_str("\\end{quote}\n\n"),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split(',')
+                        .next()
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code = Some((language, String::new()));
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                if let Some((language, code)) = code.take() {
+                    if !text.is_empty() {
+                        blocks.push(Block::Text(std::mem::take(&mut text)));
+                    }
+                    blocks.push(Block::Code {
+                        language,
+                        code,
+                        filename: filename.take(),
+                        caption: None,
+                    });
+                }
+            }
+            Event::Start(Tag::List(first)) => {
+                lists.push(first.is_some());
+                out.push_str(if first.is_some() {
+                    "\\begin{enumerate}\n"
+                } else {
+                    "\\begin{itemize}\n"
+                });
+            }
+            Event::End(Tag::List(_)) => {
+                let ordered = lists.pop().unwrap_or(false);
+                out.push_str(if ordered {
+                    "\\end{enumerate}\n\n"
+                } else {
+                    "\\end{itemize}\n\n"
+                });
+            }
+            Event::Start(Tag::Item) => out.push_str("\\item "),
+            Event::End(Tag::Item) => out.push('\n'),
+            Event::Start(Tag::Table(alignments)) => {
+                let columns = alignments
+                    .iter()
+                    .map(|alignment| match alignment {
+                        Alignment::Center => 'c',
+                        Alignment::Right => 'r',
+                        _ => 'l',
+                    })
+                    .collect::<String>();
+                out.push_str(&format!(
+                    "\\begin{{center}}\n\\begin{{tabular}}{{{}}}\n\\hline\n",
+                    columns
+                ));
+            }
+            Event::End(Tag::Table(_)) => {
+                out.push_str("\\hline\n\\end{tabular}\n\\end{center}\n\n")
+            }
+            Event::Start(Tag::TableHead | Tag::TableRow) => cell = 0,
+            Event::End(Tag::TableHead) => out.push_str(" \\\\\n\\hline\n"),
+            Event::End(Tag::TableRow) => out.push_str(" \\\\\n"),
+            Event::Start(Tag::TableCell) => {
+                if cell > 0 {
+                    out.push_str(" & ");
+                }
+                cell += 1;
+            }
+            Event::End(Tag::TableCell) => {}
+            Event::Start(Tag::Emphasis) => out.push_str("\\emph{"),
+            Event::Start(Tag::Strong) => out.push_str("\\textbf{"),
+            Event::Start(Tag::Strikethrough) => out.push_str("\\sout{"),
+            Event::End(Tag::Emphasis | Tag::Strong | Tag::Strikethrough) => {
+                out.push('}')
+            }
+            Event::Start(Tag::Link(_, url, _)) => links.push(url.to_string()),
+            Event::End(Tag::Link(..)) => {
+                // Footnotes in headings would end up in the table of contents
+                if let Some(url) = links.pop() {
+                    if !url.starts_with('#') && !in_heading {
+                        out.push_str(&format!(
+                            "\\footnote{{\\url{{{}}}}}",
+                            escape_url(&absolute_url(&url, book_url))
+                        ));
+                    }
+                }
+            }
+            Event::Start(Tag::Image(_, src, _)) => {
+                out.push_str(&image(src));
+                // The alt text would end up in the text otherwise
+                links.push(String::from("#"));
+            }
+            Event::End(Tag::Image(..)) => {
+                links.pop();
+            }
+            Event::Start(Tag::FootnoteDefinition(_))
+            | Event::End(Tag::FootnoteDefinition(_)) => {}
+            Event::Text(content) => {
+                if let Some((_, code)) = code.as_mut() {
+                    code.push_str(content);
+                } else if paragraph == ParagraphKind::Filename {
+                    out.push_str(content);
+                } else if !matches!(links.last(), Some(url) if url == "#") {
+                    out.push_str(&escape(content));
+                }
+            }
+            Event::Code(content) => {
+                out.push_str(&format!("\\texttt{{{}}}", escape(content)))
+            }
+            Event::Html(html) => {
+                // `listing_figures` puts the filename in the HTML of the
+                // figure, and the caption in a paragraph of `<figcaption>`
+                if let Some(caps) = FIGURE_FILENAME.captures(html) {
+                    filename = Some(
+                        FILENAME_PREFIX.replace(&caps[1], "").into_owned(),
+                    );
+                }
+                match html.trim() {
+                    "<figcaption>" => in_figcaption = true,
+                    "</figcaption>" => in_figcaption = false,
+                    _ => {}
+                }
+                // Only images are kept from the HTML written for the website
+                for caps in IMG_SRC.captures_iter(html) {
+                    out.push_str(&image(&caps[1]));
+                }
+            }
+            Event::SoftBreak => out.push('\n'),
+            Event::HardBreak => out.push_str("\\\\\n"),
+            Event::Rule => {
+                out.push_str("\\par\\noindent\\rule{\\linewidth}{0.4pt}\n\n")
+            }
+            Event::FootnoteReference(_) | Event::TaskListMarker(_) => {}
+        }
+    }
+    if !text.is_empty() {
+        blocks.push(Block::Text(text));
+    }
+    blocks
+}
+
+fn image(src: &str) -> String {
+    // Leave out the extension so graphicx picks the converted PDF for SVGs
+    let path = Path::new(src).with_extension("");
+    format!("\\bookimage{{{}}}\n", path.to_string_lossy())
+}
+
+fn absolute_url(url: &str, book_url: &str) -> String {
+    if url.contains("://") || url.starts_with("mailto:") {
+        url.to_string()
+    } else {
+        format!("{}{}", book_url, url)
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// `\url` takes most characters as they are, but inside a footnote `%` and `#`
+// need a backslash.
+fn escape_url(url: &str) -> String {
+    url.replace('%', "\\%").replace('#', "\\#")
+}
+
+lazy_static! {
+    static ref LISTING_MENTION: Regex = Regex::new(r"範例\s*(\d+-\d+)").unwrap();
+    static ref HEADING_NUMBERING: Regex =
+        Regex::new(r"\\(chapter|section|subsection|subsubsection|paragraph)NUMBERING\{([^\n]*)\}\n").unwrap();
+}
+
+fn render_blocks(
+    blocks: &[Block],
+    is_numbered: bool,
+    labels: &BTreeSet<String>,
+) -> String {
+    let mut tex = String::new();
+    for block in blocks {
+        match block {
+            Block::Text(text) => {
+                tex.push_str(&render_text(text, is_numbered, labels))
+            }
+            Block::Code {
+                language,
+                code,
+                filename,
+                caption,
+            } => tex.push_str(&render_code(
+                language,
+                code,
+                filename.as_deref(),
+                caption.as_ref().map(|caption| Caption {
+                    number: caption.number.clone(),
+                    text: render_references(&caption.text, labels),
+                }),
+            )),
+        }
+    }
+    tex
+}
+
+fn render_text(
+    text: &str,
+    is_numbered: bool,
+    labels: &BTreeSet<String>,
+) -> String {
+    let text = HEADING_NUMBERING.replace_all(text, |caps: &Captures| {
+        if is_numbered {
+            format!("\\{}{{{}}}\n", &caps[1], &caps[2])
+        } else {
+            let level = if &caps[1] == "paragraph" {
+                "subsubsection"
+            } else {
+                &caps[1]
+            };
+            format!(
+                "\\{}*{{{}}}\n\\addcontentsline{{toc}}{{{}}}{{{}}}\n",
+                &caps[1], &caps[2], level, &caps[2]
+            )
+        }
+    });
+    render_references(&text, labels)
+}
+
+// `範例 7-21` becomes a reference to the listing, so the number stays right
+// if the listings are renumbered.
+fn render_references(text: &str, labels: &BTreeSet<String>) -> String {
+    LISTING_MENTION
+        .replace_all(text, |caps: &Captures| {
+            if labels.contains(&caps[1]) {
+                format!("範例~\\ref{{lst:{}}}", &caps[1])
+            } else {
+                caps[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+// Languages the listings package knows, by the names code blocks use
+fn listings_language(language: &str) -> Option<&'static str> {
+    match language {
+        "rust" => Some("Rust"),
+        "toml" => Some("toml"),
+        "html" => Some("HTML"),
+        "console" | "bash" | "sh" => Some("bash"),
+        "c" => Some("C"),
+        "python" => Some("Python"),
+        "javascript" => Some("JavaScript"),
+        _ => None,
+    }
+}
+
+fn render_code(
+    language: &str,
+    code: &str,
+    filename: Option<&str>,
+    caption: Option<Caption>,
+) -> String {
+    let options = match listings_language(language) {
+        Some(language) => format!("[language={}]", language),
+        None => String::new(),
+    };
+    let listing =
+        format!("\\begin{{code}}{}\n{}\\end{{code}}\n", options, code);
+    let filename = filename
+        .map(|filename| format!("\\filename{{{}}}\n", escape(filename)))
+        .unwrap_or_default();
+
+    match caption {
+        // Give the float the book's number, in case a number was skipped
+        Some(caption) => {
+            let (_, number) = caption
+                .number
+                .split_once('-')
+                .expect("Listing numbers should have a dash");
+            let number: usize =
+                number.parse().expect("Listing numbers should be numbers");
+            format!(
+                "\\begin{{listing}}\n{}{}\\setcounter{{listing}}{{{}}}\n\\caption{{{}}}\\label{{lst:{}}}\n\\end{{listing}}\n\n",
+                filename,
+                listing,
+                number - 1,
+                caption.text,
+                caption.number
+            )
+        }
+        None => format!("{}{}\n", filename, listing),
+    }
+}
+
+fn book_document(title: &str, inputs: &[(String, bool)]) -> String {
+    let mut body = String::from("\\frontmatter\n\\tableofcontents\n");
+    let mut in_main_matter = false;
+    for (name, is_numbered) in inputs {
+        if *is_numbered && !in_main_matter {
+            body.push_str("\\mainmatter\n");
+            in_main_matter = true;
+        }
+        body.push_str(&format!("\\input{{{}}}\n", name));
+    }
+    format!(
+        "{}\\title{{{}}}\n\n\\begin{{document}}\n{}\\end{{document}}\n",
+        PREAMBLE,
+        escape(title),
+        body
+    )
+}
+
+const PREAMBLE: &str = r#"% Generated by `tools/latex.sh`; make fixes in `/src/`.
+\documentclass[11pt]{book}
+
+\usepackage{xeCJK}
+\setCJKmainfont{Noto Serif CJK TC}
+\setCJKsansfont{Noto Sans CJK TC}
+\setCJKmonofont{Noto Sans Mono CJK TC}
+
+\usepackage{graphicx}
+\usepackage{xcolor}
+\usepackage{float}
+\usepackage{listings}
+\usepackage[normalem]{ulem}
+\usepackage{hyperref}
+
+\newfloat{listing}{htbp}{lol}[chapter]
+\floatname{listing}{範例}
+\renewcommand{\thelisting}{\thechapter-\arabic{listing}}
+
+\lstdefinelanguage{toml}{
+  morecomment=[l]{\#},
+  morestring=[b]",
+}
+\lstdefinestyle{book}{
+  basicstyle=\ttfamily\small,
+  keywordstyle=\color{blue!60!black},
+  commentstyle=\color{green!40!black},
+  stringstyle=\color{red!50!black},
+  columns=fullflexible,
+  keepspaces=true,
+  breaklines=true,
+  frame=single,
+}
+\lstnewenvironment{code}[1][]{\lstset{style=book,#1}}{}
+
+\newcommand{\filename}[1]{\par\noindent{\small\textbf{檔案名稱：\texttt{#1}}}\par}
+\newcommand{\bookimage}[1]{\begin{center}
+  \includegraphics[width=0.8\linewidth,height=0.4\textheight,keepaspectratio]{#1}
+\end{center}}
+
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::{Block, Caption};
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn summary_files_numbers_only_chapters() {
+        let files = super::summary_files(
+            "[前言](foreword.md)\n[介紹](ch00-00-introduction.md)\n- [開始入門](ch01-00-getting-started.md)\n    - [附錄 A](appendix-01-keywords.md)\n",
+        );
+
+        assert_eq!(
+            files,
+            vec![
+                ("foreword.md".to_string(), false),
+                ("ch00-00-introduction.md".to_string(), false),
+                ("ch01-00-getting-started.md".to_string(), true),
+                ("appendix-01-keywords.md".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_blocks_attaches_filenames_and_captions_to_code() {
+        let blocks = super::parse_blocks(
+            "如範例 1-1 所示：\n\n<span class=\"filename\">檔案名稱：src/main.rs</span>\n\n```rust,ignore\nfn main() {}\n```\n\n<span class=\"caption\">範例 1-1：印出 `Hello, world!` 的程式</span>\n",
+            "https://rust-lang.tw/book-tw/",
+        );
+
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Text("如範例 1-1 所示：\n\n".to_string()),
+                Block::Code {
+                    language: "rust".to_string(),
+                    code: "fn main() {}\n".to_string(),
+                    filename: Some("src/main.rs".to_string()),
+                    caption: Some(Caption {
+                        number: "1-1".to_string(),
+                        text: "印出 \\texttt{Hello, world!} 的程式".to_string(),
+                    }),
+                },
+            ]
+        );
+
+        let labels = ["1-1".to_string()].into_iter().collect::<BTreeSet<_>>();
+        let tex = super::render_blocks(&blocks, true, &labels);
+        assert!(tex.starts_with("如範例~\\ref{lst:1-1} 所示："));
+        assert!(tex.contains(
+            "\\filename{src/main.rs}\n\\begin{code}[language=Rust]\nfn main() {}\n\\end{code}\n\\setcounter{listing}{0}\n\\caption{印出 \\texttt{Hello, world!} 的程式}\\label{lst:1-1}\n"
+        ));
+    }
+
+    #[test]
+    fn parse_blocks_reads_listing_figures() {
+        let blocks = super::parse_blocks(
+            "<figure class=\"listing\">\n<span class=\"filename\">檔案名稱：src/main.rs</span>\n\n```rust\nfn main() {}\n```\n\n<figcaption>\n\n範例 2-1：取得使用者的猜測數字\n\n</figcaption>\n</figure>\n",
+            "https://rust-lang.tw/book-tw/",
+        );
+
+        assert_eq!(
+            blocks,
+            vec![Block::Code {
+                language: "rust".to_string(),
+                code: "fn main() {}\n".to_string(),
+                filename: Some("src/main.rs".to_string()),
+                caption: Some(Caption {
+                    number: "2-1".to_string(),
+                    text: "取得使用者的猜測數字".to_string(),
+                }),
+            }]
+        );
+    }
+
+    #[test]
+    fn filenames_are_escaped_once() {
+        let markdown = [
+            "<span class=\"filename\">檔案名稱：src/front_of_house.rs</span>\n\n```rust\nmod hosting;\n```\n",
+            "<figure class=\"listing\">\n<span class=\"filename\">檔案名稱：src/front_of_house.rs</span>\n\n```rust\nmod hosting;\n```\n\n</figure>\n",
+        ];
+
+        for markdown in markdown {
+            let blocks =
+                super::parse_blocks(markdown, "https://rust-lang.tw/book-tw/");
+            let tex = super::render_blocks(&blocks, false, &BTreeSet::new());
+            assert!(
+                tex.starts_with("\\filename{src/front\\_of\\_house.rs}\n"),
+                "{}",
+                tex
+            );
+        }
+    }
+
+    #[test]
+    fn parse_blocks_turns_links_into_footnotes_and_escapes_text() {
+        let blocks = super::parse_blocks(
+            "## 安裝 [rustup](https://rustup.rs/)\n\n100% 的 [rustup](https://rustup.rs/) 與[錯誤處理](ch09-00-error-handling.html#a)\n",
+            "https://rust-lang.tw/book-tw/",
+        );
+        let tex = super::render_blocks(&blocks, false, &BTreeSet::new());
+
+        assert_eq!(
+            tex,
+            "\\section*{安裝 rustup}\n\\addcontentsline{toc}{section}{安裝 rustup}\n\n100\\% 的 rustup\\footnote{\\url{https://rustup.rs/}} 與錯誤處理\\footnote{\\url{https://rust-lang.tw/book-tw/ch09-00-error-handling.html\\#a}}\n\n"
+        );
+    }
+}
@@ -1,8 +1,14 @@
 // We have some long regex literals, so:
 // ignore-tidy-linelength
 
+#[macro_use]
+extern crate lazy_static;
+
 use docopt::Docopt;
-use serde::Deserialize;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
 use std::io::BufRead;
 use std::{fs, io, path};
 
@@ -11,44 +17,75 @@ fn main() {
         .and_then(|d| d.deserialize())
         .unwrap_or_else(|e| e.exit());
 
-    let src_dir = &path::Path::new(&args.arg_src_dir);
-    let found_errs = walkdir::WalkDir::new(src_dir)
-        .min_depth(1)
-        .into_iter()
-        .map(|entry| match entry {
+    let rules = match load_rules(&args.flag_config) {
+        Ok(rules) => rules,
+        Err(err) => {
+            eprintln!("Unable to load {}: {}", args.flag_config, err);
+            std::process::exit(2)
+        }
+    };
+
+    let entries = args
+        .arg_dir
+        .iter()
+        .flat_map(|dir| walkdir::WalkDir::new(dir).min_depth(1));
+    let mut found_errs = false;
+    let mut reports = Vec::new();
+    for entry in entries {
+        let entry = match entry {
             Ok(entry) => entry,
             Err(err) => {
                 eprintln!("{:?}", err);
                 std::process::exit(911)
             }
-        })
-        .map(|entry| {
-            let path = entry.path();
-            if is_file_of_interest(path) {
-                let err_vec = lint_file(path);
-                for err in &err_vec {
-                    match *err {
-                        LintingError::LineOfInterest(line_num, ref line) => {
-                            eprintln!(
-                                "{}:{}\t{}",
-                                path.display(),
-                                line_num,
-                                line
-                            )
-                        }
-                        LintingError::UnableToOpenFile => {
-                            eprintln!("Unable to open {}.", path.display())
-                        }
-                    }
+        };
+        let path = entry.path();
+        if !is_file_of_interest(path) {
+            continue;
+        }
+        if args.flag_fix {
+            match fix_file(path, &rules) {
+                Ok(0) => {}
+                Ok(count) => {
+                    eprintln!("Fixed {} line(s) in {}.", count, path.display())
+                }
+                Err(err) => {
+                    eprintln!("Unable to fix {}: {}", path.display(), err);
+                    found_errs = true;
                 }
-                !err_vec.is_empty()
-            } else {
-                false
             }
-        })
-        .collect::<Vec<_>>()
-        .iter()
-        .any(|result| *result);
+        }
+        for err in lint_file(path, &rules) {
+            found_errs = true;
+            match err {
+                LintingError::LineOfInterest(finding) => reports.push(Report {
+                    path: path.display().to_string(),
+                    finding,
+                }),
+                LintingError::UnableToOpenFile => {
+                    eprintln!("Unable to open {}.", path.display())
+                }
+            }
+        }
+    }
+
+    match args.flag_format {
+        Format::Text => {
+            for report in &reports {
+                eprintln!(
+                    "{}:{}:{}\t{}: {}",
+                    report.path,
+                    report.finding.line,
+                    report.finding.column,
+                    report.finding.rule,
+                    report.finding.message
+                )
+            }
+        }
+        Format::Json => println!("{}", render_json(&reports)),
+        Format::Sarif => println!("{}", render_sarif(&rules, &reports)),
+        Format::Github => print!("{}", render_github(&reports)),
+    }
 
     if found_errs {
         std::process::exit(1)
@@ -57,65 +94,498 @@ fn main() {
     }
 }
 
-const USAGE: &'static str = "
-counter
+const USAGE: &str = "
+lfp
+Lints the Markdown files and the `output.txt` files of listings in the given
+directories. Rules can be turned off in the config file, or for one line with a
+`<!-- lfp-disable-next-line rule -->` comment above it; leaving out the rule
+names turns off every rule for that line.
+
 Usage:
-  lfp <src-dir>
+  lfp [options] <dir>...
   lfp (-h | --help)
+
+With `--fix`, local `file://` URLs, the only problems with a mechanical fix,
+are rewritten in place before the rest is reported.
+
+Lines and columns in the output start at 1, and columns count characters.
+`text` output goes to stderr; `json`, `sarif` and `github` (workflow commands
+that GitHub Actions shows as annotations) go to stdout.
+
 Options:
-  -h --help         Show this screen.
+  -h --help           Show this screen.
+  --config=<file>     Which rules to run [default: tools/lfp.toml].
+  --format=<format>   `text`, `json`, `sarif` or `github` [default: text].
+  --fix               Fix what can be fixed.
 ";
 
 #[derive(Debug, Deserialize)]
 struct Args {
-    arg_src_dir: String,
+    arg_dir: Vec<String>,
+    flag_config: String,
+    flag_format: Format,
+    flag_fix: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Text,
+    Json,
+    Sarif,
+    Github,
+}
+
+// A line of a Markdown file, with what the lines before it tell about it.
+struct Line<'a> {
+    text: &'a str,
+    in_code_block: bool,
+    in_comment: bool,
 }
 
-fn lint_file(path: &path::Path) -> Vec<LintingError> {
+impl Line<'_> {
+    fn is_prose(&self) -> bool {
+        !self.in_code_block && !self.in_comment
+    }
+}
+
+trait Rule {
+    // What the config file and suppression comments call the rule
+    fn name(&self) -> &'static str;
+
+    // Every problem in the line
+    fn check(&self, line: &Line) -> Vec<Problem>;
+
+    // The line with the rule's problems fixed, if they can be fixed without
+    // a human deciding how
+    fn fix(&self, _text: &str) -> Option<String> {
+        None
+    }
+}
+
+// A problem a rule found, at a byte offset into the line
+#[derive(Debug)]
+struct Problem {
+    offset: usize,
+    message: String,
+}
+
+// Output in the book should show the paths `file:///projects/...` the way
+// they'd look in the reader's projects directory.
+struct FileUrl;
+
+impl Rule for FileUrl {
+    fn name(&self) -> &'static str {
+        "file-url"
+    }
+
+    fn check(&self, line: &Line) -> Vec<Problem> {
+        WORD.find_iter(line.text)
+            .filter(|word| {
+                word.as_str().contains("file://")
+                    && !word.as_str().contains("file:///projects/")
+            })
+            .map(|url| Problem {
+                offset: url.start(),
+                message: format!(
+                    "`{}` isn't in file:///projects/",
+                    url.as_str()
+                ),
+            })
+            .collect()
+    }
+
+    // Like `update-rustc.sh` does for `output.txt`, keep only the last part
+    // of the path, the crate's directory.
+    fn fix(&self, text: &str) -> Option<String> {
+        let fixed = FILE_URL.replace_all(text, |caps: &Captures| {
+            let path = caps[1].trim_matches('/');
+            match path.rsplit('/').next() {
+                Some(name)
+                    if !name.is_empty()
+                        && path != format!("projects/{}", name) =>
+                {
+                    format!("file:///projects/{}", name)
+                }
+                _ => caps[0].to_string(),
+            }
+        });
+        if fixed == text {
+            None
+        } else {
+            Some(fixed.into_owned())
+        }
+    }
+}
+
+struct TrailingWhitespace;
+
+impl Rule for TrailingWhitespace {
+    fn name(&self) -> &'static str {
+        "trailing-whitespace"
+    }
+
+    fn check(&self, line: &Line) -> Vec<Problem> {
+        let trimmed = line.text.trim_end();
+        if trimmed.len() < line.text.len() {
+            vec![Problem {
+                offset: trimmed.len(),
+                message: "line ends with whitespace".to_string(),
+            }]
+        } else {
+            Vec::new()
+        }
+    }
+
+    // No fix: two trailing spaces can be a hard line break, and code keeps
+    // whatever it has
+}
+
+// Tabs are fine in code, where rustfmt has the last word, but in prose they
+// render differently depending on the output format.
+struct TabsInProse;
+
+impl Rule for TabsInProse {
+    fn name(&self) -> &'static str {
+        "tabs-in-prose"
+    }
+
+    fn check(&self, line: &Line) -> Vec<Problem> {
+        match line.text.find('\t') {
+            Some(offset) if line.is_prose() => vec![Problem {
+                offset,
+                message: "tab in prose".to_string(),
+            }],
+            _ => Vec::new(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref WORD: Regex = Regex::new(r"\S+").unwrap();
+    static ref FILE_URL: Regex = Regex::new(r"file://([^\s)]*)").unwrap();
+    static ref URL: Regex = Regex::new(r"https?://[^\s)>\]]+").unwrap();
+    static ref LINK_DEFINITION: Regex =
+        Regex::new(r"^\s*\[[^\]]+\]:\s*$").unwrap();
+}
+
+// mdBook doesn't turn URLs into links on its own, so URLs in prose need to be
+// links. URLs in inline code or emphasis are left alone; those are examples
+// like `http://127.0.0.1:7878`, not pages to visit.
+struct BareUrl;
+
+impl Rule for BareUrl {
+    fn name(&self) -> &'static str {
+        "bare-url"
+    }
+
+    fn check(&self, line: &Line) -> Vec<Problem> {
+        if !line.is_prose() {
+            return Vec::new();
+        }
+        URL.find_iter(line.text)
+            .filter(|url| {
+                let before = &line.text[..url.start()];
+                let in_inline_code = before.matches('`').count() % 2 == 1;
+                let in_link_or_emphasis =
+                    before.ends_with(&['(', '<', '[', '*', '_'][..]);
+                !in_inline_code
+                    && !in_link_or_emphasis
+                    && !LINK_DEFINITION.is_match(before)
+            })
+            .map(|url| Problem {
+                offset: url.start(),
+                message: format!("`{}` isn't a link", url.as_str()),
+            })
+            .collect()
+    }
+}
+
+fn all_rules() -> Vec<Box<dyn Rule>> {
+    vec![
+        Box::new(FileUrl),
+        Box::new(TrailingWhitespace),
+        Box::new(TabsInProse),
+        Box::new(BareUrl),
+    ]
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Config {
+    #[serde(default)]
+    rules: BTreeMap<String, bool>,
+}
+
+// Every rule is on unless the config file turns it off.
+fn load_rules(config_path: &str) -> Result<Vec<Box<dyn Rule>>, String> {
+    let contents =
+        fs::read_to_string(config_path).map_err(|err| err.to_string())?;
+    rules_from_config(&contents)
+}
+
+fn rules_from_config(contents: &str) -> Result<Vec<Box<dyn Rule>>, String> {
+    let config: Config =
+        toml::from_str(contents).map_err(|err| err.to_string())?;
+    let mut rules = all_rules();
+    for name in config.rules.keys() {
+        if !rules.iter().any(|rule| rule.name() == name) {
+            return Err(format!("there's no rule called `{}`", name));
+        }
+    }
+    rules.retain(|rule| config.rules.get(rule.name()).copied().unwrap_or(true));
+    Ok(rules)
+}
+
+lazy_static! {
+    static ref DISABLE_NEXT_LINE: Regex =
+        Regex::new(r"<!--\s*lfp-disable-next-line\b([^>]*?)\s*-->").unwrap();
+}
+
+// The rules a `<!-- lfp-disable-next-line -->` comment turns off for the next
+// line; an empty list means all of them.
+fn disabled_rules(line: &str) -> Option<Vec<String>> {
+    DISABLE_NEXT_LINE.captures(line).map(|caps| {
+        caps[1]
+            .split_whitespace()
+            .map(|rule| rule.to_string())
+            .collect()
+    })
+}
+
+fn lint_file(path: &path::Path, rules: &[Box<dyn Rule>]) -> Vec<LintingError> {
     match fs::File::open(path) {
-        Ok(file) => lint_lines(io::BufReader::new(&file).lines()),
+        Ok(file) => lint_lines(
+            io::BufReader::new(&file).lines(),
+            FileKind::of(path),
+            rules,
+        ),
         Err(_) => vec![LintingError::UnableToOpenFile],
     }
 }
 
-fn lint_lines<I>(lines: I) -> Vec<LintingError>
+// Rewrites the file with every fixable problem fixed, returning how many lines
+// changed.
+fn fix_file(path: &path::Path, rules: &[Box<dyn Rule>]) -> io::Result<usize> {
+    let contents = fs::read_to_string(path)?;
+    let (fixed, count) = fix_text(&contents, FileKind::of(path), rules);
+    if count > 0 {
+        fs::write(path, fixed)?;
+    }
+    Ok(count)
+}
+
+fn fix_text(
+    contents: &str,
+    kind: FileKind,
+    rules: &[Box<dyn Rule>],
+) -> (String, usize) {
+    // Only fix what the rules report, so suppression comments and the config
+    // file are honored
+    let lines = contents.lines().map(|line| Ok(line.to_string()));
+    let mut to_fix: BTreeMap<usize, Vec<&'static str>> = BTreeMap::new();
+    for err in lint_lines(lines, kind, rules) {
+        if let LintingError::LineOfInterest(finding) = err {
+            to_fix.entry(finding.line).or_default().push(finding.rule);
+        }
+    }
+
+    let mut fixed = String::with_capacity(contents.len());
+    let mut count = 0;
+    for (line_num, line) in contents.split_inclusive('\n').enumerate() {
+        let text = line.trim_end_matches(&['\r', '\n'][..]);
+        let mut new_text = text.to_string();
+        if let Some(names) = to_fix.get(&(line_num + 1)) {
+            for rule in rules.iter().filter(|rule| names.contains(&rule.name()))
+            {
+                if let Some(fixed_text) = rule.fix(&new_text) {
+                    new_text = fixed_text;
+                }
+            }
+        }
+        if new_text != text {
+            count += 1;
+        }
+        fixed.push_str(&new_text);
+        fixed.push_str(&line[text.len()..]);
+    }
+    (fixed, count)
+}
+
+// Markdown files have prose, while `output.txt` files are all output, like the
+// code blocks they're included in.
+#[derive(Debug, Clone, Copy)]
+enum FileKind {
+    Markdown,
+    Output,
+}
+
+impl FileKind {
+    fn of(path: &path::Path) -> FileKind {
+        if path.extension().map_or(false, |ext| ext == "md") {
+            FileKind::Markdown
+        } else {
+            FileKind::Output
+        }
+    }
+}
+
+fn lint_lines<I>(
+    lines: I,
+    kind: FileKind,
+    rules: &[Box<dyn Rule>],
+) -> Vec<LintingError>
 where
     I: Iterator<Item = io::Result<String>>,
 {
-    lines
-        .enumerate()
-        .map(|(line_num, line)| {
-            let raw_line = line.unwrap();
-            if is_line_of_interest(&raw_line) {
-                Err(LintingError::LineOfInterest(line_num, raw_line))
-            } else {
-                Ok(())
+    let is_output = matches!(kind, FileKind::Output);
+    let mut errs = Vec::new();
+    let mut in_code_block = false;
+    let mut in_comment = false;
+    let mut disabled: Option<Vec<String>> = None;
+
+    for (line_num, line) in lines.enumerate() {
+        let raw_line = line.unwrap();
+        // Code blocks and comments can be inside block quotes, too
+        let trimmed = raw_line
+            .trim_start_matches(|c: char| c == '>' || c.is_whitespace());
+        let is_fence = trimmed.starts_with("```");
+        if is_fence {
+            in_code_block = !in_code_block;
+        }
+        let starts_comment = !in_code_block && trimmed.starts_with("<!--");
+
+        let line = Line {
+            text: &raw_line,
+            in_code_block: is_output || in_code_block || is_fence,
+            in_comment: in_comment || starts_comment,
+        };
+        let mut findings = Vec::new();
+        for rule in rules {
+            let is_disabled = disabled.as_ref().map_or(false, |names| {
+                names.is_empty() || names.iter().any(|name| name == rule.name())
+            });
+            if is_disabled {
+                continue;
             }
-        })
-        .filter(|result| result.is_err())
-        .map(|result| result.unwrap_err())
-        .collect()
+            for problem in rule.check(&line) {
+                findings.push(Finding {
+                    line: line_num + 1,
+                    column: raw_line[..problem.offset].chars().count() + 1,
+                    rule: rule.name(),
+                    message: problem.message,
+                });
+            }
+        }
+        findings.sort_by_key(|finding| finding.column);
+        errs.extend(findings.into_iter().map(LintingError::LineOfInterest));
+
+        if !in_code_block {
+            in_comment =
+                (in_comment || starts_comment) && !raw_line.contains("-->");
+        }
+        disabled = disabled_rules(&raw_line);
+    }
+
+    errs
 }
 
 fn is_file_of_interest(path: &path::Path) -> bool {
     path.extension().map_or(false, |ext| ext == "md")
-}
-
-fn is_line_of_interest(line: &str) -> bool {
-    !line
-        .split_whitespace()
-        .filter(|sub_string| {
-            sub_string.contains("file://")
-                && !sub_string.contains("file:///projects/")
-        })
-        .collect::<Vec<_>>()
-        .is_empty()
+        || path.file_name().map_or(false, |name| name == "output.txt")
 }
 
 #[derive(Debug)]
 enum LintingError {
     UnableToOpenFile,
-    LineOfInterest(usize, String),
+    LineOfInterest(Finding),
+}
+
+// Where a rule found a problem; lines and columns start at 1, and columns count
+// characters, not bytes, so they're right for Chinese text too.
+#[derive(Debug, Serialize)]
+struct Finding {
+    line: usize,
+    column: usize,
+    rule: &'static str,
+    message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    path: String,
+    #[serde(flatten)]
+    finding: Finding,
+}
+
+fn render_json(reports: &[Report]) -> String {
+    serde_json::to_string_pretty(reports).expect("Reports should serialize")
+}
+
+// SARIF 2.1.0, which code scanning and editors read
+fn render_sarif(rules: &[Box<dyn Rule>], reports: &[Report]) -> String {
+    let rules = rules
+        .iter()
+        .map(|rule| json!({ "id": rule.name() }))
+        .collect::<Vec<_>>();
+    let results = reports
+        .iter()
+        .map(|report| {
+            json!({
+                "ruleId": report.finding.rule,
+                "level": "error",
+                "message": { "text": report.finding.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": report.path },
+                        "region": {
+                            "startLine": report.finding.line,
+                            "startColumn": report.finding.column,
+                        },
+                    },
+                }],
+            })
+        })
+        .collect::<Vec<_>>();
+    let log = json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": "lfp", "rules": rules } },
+            "columnKind": "unicodeCodePoints",
+            "results": results,
+        }],
+    });
+    serde_json::to_string_pretty(&log).expect("SARIF should serialize")
+}
+
+// GitHub Actions workflow commands, one `::error` per finding
+fn render_github(reports: &[Report]) -> String {
+    reports
+        .iter()
+        .map(|report| {
+            format!(
+                "::error file={},line={},col={},title={}::{}\n",
+                escape_github_property(&report.path),
+                report.finding.line,
+                report.finding.column,
+                escape_github_property(&format!("lfp {}", report.finding.rule)),
+                escape_github_data(&report.finding.message)
+            )
+        })
+        .collect()
+}
+
+fn escape_github_data(data: &str) -> String {
+    data.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(property: &str) -> String {
+    escape_github_data(property)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
 }
 
 #[cfg(test)]
@@ -123,6 +593,27 @@ mod tests {
 
     use std::path;
 
+    fn check(rule: &dyn super::Rule, text: &str) -> Vec<super::Problem> {
+        rule.check(&super::Line {
+            text,
+            in_code_block: false,
+            in_comment: false,
+        })
+    }
+
+    fn lint(text: &str) -> Vec<(usize, &'static str)> {
+        let lines = text.lines().map(|line| Ok(line.to_string()));
+        super::lint_lines(lines, super::FileKind::Markdown, &super::all_rules())
+            .into_iter()
+            .map(|err| match err {
+                super::LintingError::LineOfInterest(finding) => {
+                    (finding.line, finding.rule)
+                }
+                super::LintingError::UnableToOpenFile => unreachable!(),
+            })
+            .collect()
+    }
+
     #[test]
     fn lint_file_returns_a_vec_with_errs_when_lines_of_interest_are_found() {
         let string = r#"
@@ -168,7 +659,9 @@ mod tests {
         let raw_lines = string.to_string();
         let lines = raw_lines.lines().map(|line| Ok(line.to_string()));
 
-        let result_vec = super::lint_lines(lines);
+        let rules: Vec<Box<dyn super::Rule>> = vec![Box::new(super::FileUrl)];
+        let result_vec =
+            super::lint_lines(lines, super::FileKind::Markdown, &rules);
 
         assert!(!result_vec.is_empty());
         assert_eq!(3, result_vec.len());
@@ -201,7 +694,9 @@ mod tests {
         let raw_lines = string.to_string();
         let lines = raw_lines.lines().map(|line| Ok(line.to_string()));
 
-        let result_vec = super::lint_lines(lines);
+        let rules: Vec<Box<dyn super::Rule>> = vec![Box::new(super::FileUrl)];
+        let result_vec =
+            super::lint_lines(lines, super::FileKind::Markdown, &rules);
 
         assert!(result_vec.is_empty());
     }
@@ -234,19 +729,155 @@ mod tests {
     }
 
     #[test]
-    fn is_line_of_interest_does_not_report_a_line_if_the_line_contains_a_file_url_which_is_directly_followed_by_the_project_path(
+    fn file_url_does_not_report_a_line_if_the_line_contains_a_file_url_which_is_directly_followed_by_the_project_path(
     ) {
         let sample_line =
             "Compiling guessing_game v0.1.0 (file:///projects/guessing_game)";
 
-        assert!(!super::is_line_of_interest(sample_line));
+        assert!(check(&super::FileUrl, sample_line).is_empty());
     }
 
     #[test]
-    fn is_line_of_interest_reports_a_line_if_the_line_contains_a_file_url_which_is_not_directly_followed_by_the_project_path(
+    fn file_url_reports_a_line_if_the_line_contains_a_file_url_which_is_not_directly_followed_by_the_project_path(
     ) {
         let sample_line = "Compiling guessing_game v0.1.0 (file:///home/you/projects/guessing_game)";
 
-        assert!(super::is_line_of_interest(sample_line));
+        assert_eq!(check(&super::FileUrl, sample_line).len(), 1);
+    }
+
+    #[test]
+    fn bare_url_only_reports_urls_that_are_not_links_code_or_emphasis() {
+        assert_eq!(
+            check(&super::BareUrl, "請見 https://crates.io/ 的說明").len(),
+            1
+        );
+        assert!(check(&super::BareUrl, "[crates.io](https://crates.io/)")
+            .is_empty());
+        assert!(check(&super::BareUrl, "<https://crates.io/>").is_empty());
+        assert!(
+            check(&super::BareUrl, "請求 *http://127.0.0.1:7878/*").is_empty()
+        );
+        assert!(check(&super::BareUrl, "執行 `curl http://127.0.0.1:7878`")
+            .is_empty());
+        assert!(
+            check(&super::BareUrl, "[rustup]: https://rustup.rs/").is_empty()
+        );
+    }
+
+    #[test]
+    fn lint_lines_skips_code_blocks_and_comments_for_prose_rules() {
+        let text = "```text\n\tcode\n```\n<!-- manual-regeneration\ncurl http://127.0.0.1:7878\n-->\n散文\t有 tab";
+
+        assert_eq!(lint(text), vec![(7, "tabs-in-prose")]);
+    }
+
+    #[test]
+    fn lint_lines_honors_disable_next_line_comments() {
+        let text = "<!-- lfp-disable-next-line bare-url -->\n請見 https://crates.io/ \n<!-- lfp-disable-next-line -->\n請見 https://crates.io/ ";
+
+        assert_eq!(lint(text), vec![(2, "trailing-whitespace")]);
+    }
+
+    #[test]
+    fn rules_from_config_turns_rules_off_and_rejects_unknown_ones() {
+        let rules =
+            super::rules_from_config("[rules]\nbare-url = false\n").unwrap();
+        let names: Vec<_> = rules.iter().map(|rule| rule.name()).collect();
+
+        assert_eq!(
+            names,
+            vec!["file-url", "trailing-whitespace", "tabs-in-prose"]
+        );
+        assert!(
+            super::rules_from_config("[rules]\nbare-urls = false\n").is_err()
+        );
+    }
+
+    #[test]
+    fn lint_lines_reports_columns_in_characters() {
+        let lines =
+            vec![Ok("請見 http://example.com/".to_string())].into_iter();
+        let errs = super::lint_lines(
+            lines,
+            super::FileKind::Markdown,
+            &super::all_rules(),
+        );
+
+        match &errs[..] {
+            [super::LintingError::LineOfInterest(finding)] => {
+                assert_eq!((finding.line, finding.column), (1, 4));
+            }
+            _ => panic!("Expected one finding, got {:?}", errs),
+        }
+    }
+
+    #[test]
+    fn render_github_escapes_workflow_command_separators() {
+        let reports = vec![super::Report {
+            path: "src/a,b.md".to_string(),
+            finding: super::Finding {
+                line: 3,
+                column: 2,
+                rule: "bare-url",
+                message: "100%\nsure".to_string(),
+            },
+        }];
+
+        assert_eq!(
+            super::render_github(&reports),
+            "::error file=src/a%2Cb.md,line=3,col=2,title=lfp bare-url::100%25%0Asure\n"
+        );
+    }
+
+    #[test]
+    fn is_file_of_interest_returns_true_for_listing_output() {
+        let interesting_fn =
+            "listings/ch02-guessing-game-tutorial/listing-02-01/output.txt";
+
+        assert!(super::is_file_of_interest(path::Path::new(interesting_fn)));
+    }
+
+    #[test]
+    fn fix_text_moves_local_file_urls_to_the_projects_directory() {
+        let contents = "$ cargo run\r\n   Compiling guessing_game v0.1.0 (file:///home/you/projects/guessing_game)\r\n   Compiling adder v0.1.0 (file:///projects/adder)\r\n";
+
+        let (fixed, count) = super::fix_text(
+            contents,
+            super::FileKind::Output,
+            &super::all_rules(),
+        );
+
+        assert_eq!(count, 1);
+        assert_eq!(
+            fixed,
+            "$ cargo run\r\n   Compiling guessing_game v0.1.0 (file:///projects/guessing_game)\r\n   Compiling adder v0.1.0 (file:///projects/adder)\r\n"
+        );
+    }
+
+    #[test]
+    fn fix_text_keeps_hard_line_breaks() {
+        let contents = "第一行  \n第二行 file:///home/you/projects/foo\n";
+
+        let (fixed, count) = super::fix_text(
+            contents,
+            super::FileKind::Markdown,
+            &super::all_rules(),
+        );
+
+        assert_eq!(count, 1);
+        assert_eq!(fixed, "第一行  \n第二行 file:///projects/foo\n");
+    }
+
+    #[test]
+    fn fix_text_leaves_suppressed_lines_alone() {
+        let contents = "<!-- lfp-disable-next-line file-url -->\nfile:///home/you/projects/foo\n";
+
+        let (fixed, count) = super::fix_text(
+            contents,
+            super::FileKind::Markdown,
+            &super::all_rules(),
+        );
+
+        assert_eq!((fixed.as_str(), count), (contents, 0));
     }
 }
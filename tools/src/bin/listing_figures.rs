@@ -0,0 +1,289 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+use std::error::Error;
+use std::io::{self, Read};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    // Every renderer gets the figures. `latex` and `epub` read them from the
+    // Markdown, and `remove_markup` takes them out for No Starch.
+    if args.cmd_supports {
+        return Ok(());
+    }
+
+    // mdbook passes `[context, book]` and expects the book back
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    let (_context, mut book): (Value, Value) = serde_json::from_str(&input)?;
+    if let Some(sections) = book.get_mut("sections") {
+        add_figures_to_items(sections);
+    }
+    serde_json::to_writer(io::stdout(), &book)?;
+
+    Ok(())
+}
+
+const USAGE: &str = "
+listing_figures
+An mdbook preprocessor that wraps the code blocks of listings, the ones with
+a filename or a caption around them, in a `<figure>` with a `<figcaption>`.
+Captions without a number get the one of the listing directory the code
+block includes, and listings without a filename get the file it includes
+with `rustdoc_include`. So it has to run before `links`.
+
+Usage:
+  listing_figures
+  listing_figures supports <renderer>
+  listing_figures (-h | --help)
+
+Options:
+  -h --help  Show this screen.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    cmd_supports: bool,
+}
+
+lazy_static! {
+    static ref LISTING_INCLUDE: Regex = Regex::new(
+        r"\{\{#(rustdoc_)?include \.\./listings/[^/]+/([^/]+)/([^}:\s]*)"
+    )
+    .unwrap();
+    static ref LISTING_DIR: Regex =
+        Regex::new(r"^listing-(\d+)-(\d+)$").unwrap();
+    static ref FILENAME: Regex =
+        Regex::new(r#"^<span class="filename">(.*)</span>$"#).unwrap();
+    static ref NUMBERED: Regex = Regex::new(r"^範例 \d+-\d+").unwrap();
+}
+
+fn add_figures_to_items(items: &mut Value) {
+    for item in items.as_array_mut().into_iter().flatten() {
+        if let Some(chapter) = item.get_mut("Chapter") {
+            if let Some(Value::String(content)) = chapter.get_mut("content") {
+                *content = add_figures(content);
+            }
+            if let Some(sub_items) = chapter.get_mut("sub_items") {
+                add_figures_to_items(sub_items);
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Figure {
+    // Lines the figure replaces, the end is exclusive
+    lines: (usize, usize),
+    // Lines of the code block
+    code: (usize, usize),
+    filename: Option<String>,
+    caption: Option<String>,
+}
+
+// Wraps the code blocks that aren't nested in anything
+fn add_figures(markdown: &str) -> String {
+    let lines = markdown.lines().collect::<Vec<_>>();
+    let mut output = Vec::new();
+    let mut position = 0;
+    for figure in figures(&lines) {
+        output.extend(
+            lines[position..figure.lines.0]
+                .iter()
+                .map(|line| line.to_string()),
+        );
+        output.push(String::from("<figure class=\"listing\">"));
+        if let Some(filename) = &figure.filename {
+            output
+                .push(format!("<span class=\"filename\">{}</span>", filename));
+        }
+        output.push(String::new());
+        output.extend(
+            lines[figure.code.0..figure.code.1]
+                .iter()
+                .map(|line| line.to_string()),
+        );
+        output.push(String::new());
+        if let Some(caption) = &figure.caption {
+            output.push(String::from("<figcaption>"));
+            output.push(String::new());
+            output.push(caption.clone());
+            output.push(String::new());
+            output.push(String::from("</figcaption>"));
+        }
+        output.push(String::from("</figure>"));
+        position = figure.lines.1;
+    }
+    output.extend(lines[position..].iter().map(|line| line.to_string()));
+
+    let mut output = output.join("\n");
+    if markdown.ends_with('\n') {
+        output.push('\n');
+    }
+    output
+}
+
+fn figures(lines: &[&str]) -> Vec<Figure> {
+    let mut figures = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        if !lines[index].starts_with("```") {
+            index += 1;
+            continue;
+        }
+        let start = index;
+        let end = match lines[start + 1..]
+            .iter()
+            .position(|line| line.starts_with("```"))
+        {
+            Some(offset) => start + 1 + offset + 1,
+            None => break,
+        };
+        index = end;
+
+        let (derived_filename, number) = derive(&lines[start..end]);
+
+        // A filename written by hand is the paragraph right above
+        let mut figure_start = start;
+        let mut filename = None;
+        if start >= 2 && lines[start - 1].trim().is_empty() {
+            if let Some(caps) = FILENAME.captures(lines[start - 2]) {
+                if start == 2 || lines[start - 3].trim().is_empty() {
+                    filename = Some(caps[1].to_string());
+                    figure_start = start - 2;
+                }
+            }
+        }
+
+        // And a caption the paragraph right below
+        let mut figure_end = end;
+        let mut caption = None;
+        if end + 1 < lines.len() && lines[end].trim().is_empty() {
+            if let Some((text, length)) = caption_at(&lines[end + 1..]) {
+                caption = Some(match &number {
+                    Some(number) if !NUMBERED.is_match(&text) => {
+                        format!("範例 {}：{}", number, text)
+                    }
+                    _ => text,
+                });
+                figure_end = end + 1 + length;
+            }
+        }
+
+        // Code blocks without a caption are often only part of a listing
+        if caption.is_some() && filename.is_none() {
+            filename = derived_filename;
+        }
+        if filename.is_some() || caption.is_some() {
+            figures.push(Figure {
+                lines: (figure_start, figure_end),
+                code: (start, end),
+                filename,
+                caption,
+            });
+        }
+    }
+    figures
+}
+
+// The text of a caption that starts at the first line, which can go on for a
+// few lines until `</span>`, and how many lines it has
+fn caption_at(lines: &[&str]) -> Option<(String, usize)> {
+    let first = lines.first()?.strip_prefix(r#"<span class="caption">"#)?;
+    let mut text = vec![first];
+    let mut length = 1;
+    while !text.last()?.trim_end().ends_with("</span>") {
+        let line = lines.get(length)?;
+        if line.trim().is_empty() {
+            return None;
+        }
+        text.push(line);
+        length += 1;
+    }
+    let text = text.join("\n");
+    let text = text.trim_end().strip_suffix("</span>")?.trim_end();
+    Some((text.to_string(), length))
+}
+
+// The filename and the listing number of a code block that includes a single
+// file of a listing. Only Rust code is included with `rustdoc_include`, the
+// rest can be output or files whose name the text already gives.
+fn derive(code: &[&str]) -> (Option<String>, Option<String>) {
+    let mut includes = code
+        .iter()
+        .flat_map(|line| LISTING_INCLUDE.captures_iter(line))
+        .map(|caps| {
+            (
+                caps.get(1).is_some(),
+                caps[2].to_string(),
+                caps[3].to_string(),
+            )
+        })
+        .collect::<Vec<_>>();
+    includes.dedup();
+    let (is_rustdoc, dir, file) = match includes.as_slice() {
+        [(is_rustdoc, dir, file)] => (is_rustdoc, dir, file),
+        _ => return (None, None),
+    };
+
+    let filename = if *is_rustdoc {
+        Some(format!("檔案名稱：{}", file))
+    } else {
+        None
+    };
+    let number = LISTING_DIR.captures(dir).map(|caps| {
+        format!(
+            "{}-{}",
+            caps[1].parse::<u32>().unwrap(),
+            caps[2].parse::<u32>().unwrap()
+        )
+    });
+    (filename, number)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn figures_get_filenames_and_numbers_from_includes() {
+        let markdown = "如範例 2-1 所示：\n\n```rust,ignore\n{{#rustdoc_include ../listings/ch02-guessing-game-tutorial/listing-02-01/src/main.rs:all}}\n```\n\n<span class=\"caption\">取得使用者的猜測數字並顯示出來</span>\n\n接下來";
+
+        assert_eq!(
+            super::add_figures(markdown),
+            "如範例 2-1 所示：\n\n<figure class=\"listing\">\n<span class=\"filename\">檔案名稱：src/main.rs</span>\n\n```rust,ignore\n{{#rustdoc_include ../listings/ch02-guessing-game-tutorial/listing-02-01/src/main.rs:all}}\n```\n\n<figcaption>\n\n範例 2-1：取得使用者的猜測數字並顯示出來\n\n</figcaption>\n</figure>\n\n接下來"
+        );
+    }
+
+    #[test]
+    fn figures_use_what_is_written_by_hand() {
+        let markdown = "<span class=\"filename\">檔案名稱：Cargo.toml</span>\n\n```toml\n{{#include ../listings/ch14-more-about-cargo/listing-14-07/add/Cargo.toml}}\n```\n\n<span class=\"caption\">`add` 工作空間的 *Cargo.toml*</span>\n";
+
+        assert_eq!(
+            super::add_figures(markdown),
+            "<figure class=\"listing\">\n<span class=\"filename\">檔案名稱：Cargo.toml</span>\n\n```toml\n{{#include ../listings/ch14-more-about-cargo/listing-14-07/add/Cargo.toml}}\n```\n\n<figcaption>\n\n範例 14-7：`add` 工作空間的 *Cargo.toml*\n\n</figcaption>\n</figure>\n"
+        );
+    }
+
+    #[test]
+    fn captions_can_span_lines() {
+        let markdown = "```rust,ignore\n{{#rustdoc_include ../listings/ch12-an-io-project/listing-12-17/src/lib.rs:here}}\n```\n\n<span class=\"caption\">範例 12-17：在 `contents` 中遍歷每一行\n</span>\n\n`lines` 方法會回傳疊代器。";
+
+        assert_eq!(
+            super::add_figures(markdown),
+            "<figure class=\"listing\">\n<span class=\"filename\">檔案名稱：src/lib.rs</span>\n\n```rust,ignore\n{{#rustdoc_include ../listings/ch12-an-io-project/listing-12-17/src/lib.rs:here}}\n```\n\n<figcaption>\n\n範例 12-17：在 `contents` 中遍歷每一行\n\n</figcaption>\n</figure>\n\n`lines` 方法會回傳疊代器。"
+        );
+    }
+
+    #[test]
+    fn code_blocks_without_filename_or_caption_are_left_alone() {
+        let markdown = "```console\n{{#include ../listings/ch01-getting-started/listing-01-01/output.txt}}\n```\n\n```rust\n{{#rustdoc_include ../listings/ch02-guessing-game-tutorial/listing-02-01/src/main.rs:here}}\n```\n";
+
+        assert_eq!(super::add_figures(markdown), markdown);
+    }
+}
@@ -0,0 +1,815 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use similar::{capture_diff_slices, Algorithm, DiffTag, TextDiff};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::ops::Range;
+use unicode_width::UnicodeWidthChar;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let manuscript = fs::read_to_string(&args.arg_manuscript)?;
+    let mut files = Vec::new();
+    for path in &args.arg_src_file {
+        files.push((path.as_str(), fs::read_to_string(path)?));
+    }
+    let files = files
+        .iter()
+        .map(|(path, contents)| (*path, contents.as_str()))
+        .collect::<Vec<_>>();
+
+    let comparison = compare(&files, &args.arg_manuscript, &manuscript);
+    print!("{}", comparison.patch);
+    for item in &comparison.review {
+        eprintln!("{}", item);
+    }
+    eprintln!(
+        "{} edit(s) in the patch, {} paragraph(s) to review",
+        comparison.edits,
+        comparison.review.len()
+    );
+
+    Ok(())
+}
+
+const USAGE: &str = "
+manuscript_diff
+Aligns a manuscript No Starch edited, converted with `tools/doc-to-md.sh`,
+with the source files it was made from, paragraph by paragraph. Edits that
+can be applied back to the source files are printed as a patch for
+`git apply`. The rest, and paragraphs only one side has, are listed for
+review on stderr.
+
+Usage:
+  manuscript_diff <manuscript> <src-file>...
+  manuscript_diff (-h | --help)
+
+Options:
+  -h --help  Show this screen.
+
+The source files are compared the way `tools/nostarch.sh` transforms them,
+with links printed, hidden lines removed and the markup of filenames and
+captions left out. Changes to whitespace alone are ignored, since wrapping
+the manuscript loses them, and so are code blocks that include listings.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_manuscript: String,
+    arg_src_file: Vec<String>,
+}
+
+lazy_static! {
+    static ref REFERENCE_DEFINITION: Regex =
+        Regex::new(r"^ {0,3}\[([^\]]+)\]:[[:blank:]]*(.*)$").unwrap();
+    static ref LIST_MARKER: Regex =
+        Regex::new(r"^(\s*)([-*+]|\d+\.)\s+").unwrap();
+    static ref SPAN_START: Regex =
+        Regex::new(r#"^<span class="(filename|caption)">"#).unwrap();
+    static ref INLINE: Regex = Regex::new(concat!(
+        r"(?P<code>`[^`\n]*`)",
+        r"|\[(?P<name>[^\]]+)\]",
+        r#"(?:\([[:blank:]]*(?P<url>[^")]*[^ ")])(?:[[:blank:]]*"[^"]*")?\)"#,
+        r"|\[(?P<key>[^\]]*)\])?",
+        r"(?P<ignore>(?i:<!--\signore\s-->))?",
+    ))
+    .unwrap();
+    static ref NOT_A_LINK: Regex =
+        Regex::new(r"^(derive\(|profile|test|no_mangle|E\d{4}$)").unwrap();
+    static ref ONLY_HTML: Regex =
+        Regex::new(r"^(?s:\s*(<!--.*?-->|<[^>]*>))*\s*$").unwrap();
+}
+
+struct Comparison {
+    patch: String,
+    review: Vec<String>,
+    edits: usize,
+}
+
+// A paragraph or code block of the manuscript
+struct Paragraph {
+    line: usize,
+    is_code: bool,
+    text: String,
+}
+
+// A paragraph or code block of a source file, transformed the way it was for
+// the manuscript. Code blocks that include listings can't be compared and
+// have no transformed text.
+struct Source {
+    file: usize,
+    line: usize,
+    is_code: bool,
+    transformed: Option<Transformed>,
+}
+
+// A piece of transformed text and where in the source file it comes from.
+// Only verbatim pieces are the same in both.
+struct Piece {
+    original: Range<usize>,
+    transformed: Range<usize>,
+    verbatim: bool,
+}
+
+#[derive(Default)]
+struct Transformed {
+    text: String,
+    pieces: Vec<Piece>,
+}
+
+impl Transformed {
+    fn push(&mut self, original: Range<usize>, text: &str, verbatim: bool) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.pieces.push(Piece {
+            original,
+            transformed: start..self.text.len(),
+            verbatim,
+        });
+    }
+
+    fn keep(&mut self, file: &str, original: Range<usize>) {
+        let text = &file[original.clone()];
+        self.push(original, text, true);
+    }
+
+    // Where an edit of the transformed text goes in the source file, if it
+    // only touches verbatim pieces. Insertions next to markup could go on
+    // either side of it, like before or into the text of a link.
+    fn original_range(&self, edit: &Range<usize>) -> Option<Range<usize>> {
+        if self.pieces.iter().any(|piece| {
+            !piece.verbatim
+                && if edit.is_empty() {
+                    piece.transformed.start <= edit.start
+                        && edit.start <= piece.transformed.end
+                } else {
+                    piece.transformed.start < edit.end
+                        && edit.start < piece.transformed.end
+                }
+        }) {
+            return None;
+        }
+        let verbatim = || self.pieces.iter().filter(|piece| piece.verbatim);
+        let start = verbatim()
+            .find(|piece| piece.transformed.contains(&edit.start))
+            .or_else(|| {
+                verbatim().find(|piece| piece.transformed.end == edit.start)
+            })?;
+        let start = start.original.start + edit.start - start.transformed.start;
+        if edit.is_empty() {
+            return Some(start..start);
+        }
+        let end = verbatim().find(|piece| {
+            piece.transformed.start < edit.end
+                && edit.end <= piece.transformed.end
+        })?;
+        Some(start..end.original.start + edit.end - end.transformed.start)
+    }
+}
+
+fn compare(
+    files: &[(&str, &str)],
+    manuscript_path: &str,
+    manuscript: &str,
+) -> Comparison {
+    let sources = files
+        .iter()
+        .enumerate()
+        .flat_map(|(index, (_, contents))| source_paragraphs(index, contents))
+        .collect::<Vec<_>>();
+    let paragraphs = manuscript_paragraphs(manuscript);
+
+    let source_keys = sources
+        .iter()
+        .map(|source| match &source.transformed {
+            Some(transformed) => key(source.is_code, &transformed.text),
+            None => key(true, ""),
+        })
+        .collect::<Vec<_>>();
+    let paragraph_keys = paragraphs
+        .iter()
+        .map(|paragraph| key(paragraph.is_code, &paragraph.text))
+        .collect::<Vec<_>>();
+
+    let mut replacements = vec![Vec::new(); files.len()];
+    let mut review = Vec::new();
+    let location =
+        |source: &Source| format!("{}:{}", files[source.file].0, source.line);
+    let mut last_source = None;
+
+    let ops =
+        capture_diff_slices(Algorithm::Myers, &source_keys, &paragraph_keys);
+    for op in ops {
+        let (tag, old, new) = op.as_tag_tuple();
+        let pairs = match tag {
+            DiffTag::Equal => old.zip(new).map(Pairing::Both).collect(),
+            _ => pair_up(old, new, &source_keys, &paragraph_keys),
+        };
+        for pairing in pairs {
+            match pairing {
+                Pairing::Both((i, j)) => {
+                    last_source = Some(i);
+                    let source = &sources[i];
+                    let transformed = match &source.transformed {
+                        Some(transformed) => transformed,
+                        None => continue,
+                    };
+                    let edits = edits(
+                        &transformed.text,
+                        &paragraphs[j].text,
+                        !source.is_code,
+                    );
+                    let mapped = edits
+                        .iter()
+                        .map(|(range, text)| {
+                            transformed
+                                .original_range(range)
+                                .map(|original| (original, text.clone()))
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    match mapped {
+                        Some(mapped) => {
+                            replacements[source.file].extend(mapped)
+                        }
+                        None => review.push(format!(
+                            "{}: edited where a link, a hidden line or other \
+                             markup is, apply by hand:\n{}",
+                            location(source),
+                            quote(&paragraphs[j].text)
+                        )),
+                    }
+                }
+                Pairing::Source(i) => {
+                    last_source = Some(i);
+                    review.push(format!(
+                        "{}: not in the manuscript",
+                        location(&sources[i])
+                    ));
+                }
+                Pairing::Manuscript(j) => {
+                    let after = match last_source {
+                        Some(i) => format!(", after {}", location(&sources[i])),
+                        None => String::new(),
+                    };
+                    review.push(format!(
+                        "{}:{}: only in the manuscript{}:\n{}",
+                        manuscript_path,
+                        paragraphs[j].line,
+                        after,
+                        quote(&paragraphs[j].text)
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut patch = String::new();
+    let mut count = 0;
+    for ((path, contents), mut replacements) in files.iter().zip(replacements) {
+        if replacements.is_empty() {
+            continue;
+        }
+        count += replacements.len();
+        replacements.sort_by_key(|(range, _)| range.start);
+        let mut changed = String::new();
+        let mut position = 0;
+        for (range, text) in replacements {
+            changed.push_str(&contents[position..range.start]);
+            changed.push_str(&text);
+            position = range.end;
+        }
+        changed.push_str(&contents[position..]);
+        patch.push_str(
+            &TextDiff::from_lines(*contents, &changed)
+                .unified_diff()
+                .header(&format!("a/{}", path), &format!("b/{}", path))
+                .to_string(),
+        );
+    }
+
+    Comparison {
+        patch,
+        review,
+        edits: count,
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Pairing {
+    Both((usize, usize)),
+    Source(usize),
+    Manuscript(usize),
+}
+
+// Pairs up the paragraphs of a changed stretch that are similar enough to
+// be edits of each other, in order
+fn pair_up(
+    mut old: Range<usize>,
+    mut new: Range<usize>,
+    old_keys: &[String],
+    new_keys: &[String],
+) -> Vec<Pairing> {
+    let similar = |i: usize, j: usize| {
+        let (old_key, new_key) = (&old_keys[i], &new_keys[j]);
+        old_key.starts_with("```") == new_key.starts_with("```")
+            && TextDiff::from_chars(old_key.as_str(), new_key.as_str()).ratio()
+                >= 0.5
+    };
+    let mut pairs = Vec::new();
+    while !old.is_empty() && !new.is_empty() {
+        if similar(old.start, new.start) {
+            pairs.push(Pairing::Both((old.start, new.start)));
+            old.start += 1;
+            new.start += 1;
+        } else if new.len() > 1 && similar(old.start, new.start + 1) {
+            pairs.push(Pairing::Manuscript(new.start));
+            new.start += 1;
+        } else {
+            pairs.push(Pairing::Source(old.start));
+            old.start += 1;
+        }
+    }
+    pairs.extend(old.map(Pairing::Source));
+    pairs.extend(new.map(Pairing::Manuscript));
+    pairs
+}
+
+// What paragraphs are aligned by. Code blocks all look the same, since the
+// source often only includes the code.
+fn key(is_code: bool, text: &str) -> String {
+    if is_code {
+        String::from("```")
+    } else {
+        text.split_whitespace().collect()
+    }
+}
+
+fn quote(text: &str) -> String {
+    text.lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// The byte ranges in `old` to replace, with their replacements
+fn edits(
+    old: &str,
+    new: &str,
+    ignore_whitespace: bool,
+) -> Vec<(Range<usize>, String)> {
+    let offsets = |text: &str| {
+        text.char_indices()
+            .map(|(i, _)| i)
+            .chain(Some(text.len()))
+            .collect::<Vec<_>>()
+    };
+    let (old_offsets, new_offsets) = (offsets(old), offsets(new));
+    TextDiff::from_chars(old, new)
+        .ops()
+        .iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| {
+            let (_, old_range, new_range) = op.as_tag_tuple();
+            (
+                old_offsets[old_range.start]..old_offsets[old_range.end],
+                new[new_offsets[new_range.start]..new_offsets[new_range.end]]
+                    .to_string(),
+            )
+        })
+        .filter_map(|(range, text)| {
+            if ignore_whitespace {
+                without_line_breaks(old, range, text)
+            } else {
+                Some((range, text))
+            }
+        })
+        .collect()
+}
+
+// Leaves the line breaks of the source alone, since the manuscript is
+// wrapped differently, and drops edits of nothing but whitespace
+fn without_line_breaks(
+    old: &str,
+    mut range: Range<usize>,
+    mut text: String,
+) -> Option<(Range<usize>, String)> {
+    let old_text = &old[range.clone()];
+    let leading = &old_text[..old_text.len() - old_text.trim_start().len()];
+    if leading.contains('\n') {
+        range.start += leading.len();
+        text = text.trim_start().to_string();
+    }
+    let old_text = &old[range.clone()];
+    let trailing = &old_text[old_text.trim_end().len()..];
+    if trailing.contains('\n') {
+        range.end -= trailing.len();
+        text = text.trim_end().to_string();
+    }
+    if old[range.clone()].trim().is_empty() && text.trim().is_empty() {
+        None
+    } else {
+        Some((range, text))
+    }
+}
+
+fn manuscript_paragraphs(manuscript: &str) -> Vec<Paragraph> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut current: Option<Paragraph> = None;
+    for (index, line) in manuscript.lines().enumerate() {
+        let in_code = current.as_ref().map_or(false, |p| p.is_code);
+        if in_code {
+            let paragraph = current.as_mut().unwrap();
+            paragraph.text.push('\n');
+            paragraph.text.push_str(line);
+            if line.starts_with("```") {
+                paragraphs.extend(current.take());
+            }
+        } else if line.starts_with("```") {
+            paragraphs.extend(current.take());
+            current = Some(Paragraph {
+                line: index + 1,
+                is_code: true,
+                text: line.to_string(),
+            });
+        } else if is_blank(line) {
+            paragraphs.extend(current.take());
+        } else if let Some(paragraph) = &mut current {
+            unwrap_line(&mut paragraph.text, line);
+        } else {
+            current = Some(Paragraph {
+                line: index + 1,
+                is_code: false,
+                text: line.to_string(),
+            });
+        }
+    }
+    paragraphs.extend(current);
+    // `docx_to_md` puts one before every chapter title
+    paragraphs.retain(|paragraph| {
+        paragraph.is_code
+            || (paragraph.text != "[TOC]"
+                && !ONLY_HTML.is_match(&paragraph.text))
+    });
+    paragraphs
+}
+
+// Block quotes are split into paragraphs like the rest
+fn is_blank(line: &str) -> bool {
+    let line = line.trim();
+    line.is_empty() || line == ">"
+}
+
+// Joins a wrapped line back onto its paragraph. Wrapping drops the space
+// between words, but there's none between CJK characters. List items stay
+// on lines of their own, and the lines of block quotes lose their markers
+// like they do in the source.
+fn unwrap_line(text: &mut String, line: &str) {
+    let is_wide = |c: Option<char>| c.and_then(|c| c.width()) == Some(2);
+    let line = match line.strip_prefix('>') {
+        Some(rest) if text.starts_with('>') => {
+            rest.strip_prefix(' ').unwrap_or(rest)
+        }
+        _ => line,
+    };
+    if LIST_MARKER.is_match(line) {
+        text.push('\n');
+    } else if !is_wide(text.chars().last()) && !is_wide(line.chars().next()) {
+        text.push(' ');
+    }
+    text.push_str(line);
+}
+
+fn source_paragraphs(file: usize, contents: &str) -> Vec<Source> {
+    let lines = contents.lines().collect::<Vec<_>>();
+    let references = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let caps = REFERENCE_DEFINITION.captures(line)?;
+            let mut url = caps[2].trim();
+            if url.is_empty() {
+                url = lines.get(index + 1).map_or("", |line| line.trim());
+            }
+            Some((caps[1].to_uppercase(), url.to_string()))
+        })
+        .collect::<HashMap<_, _>>();
+
+    let mut sources = Vec::new();
+    let mut push = |range: Range<usize>, is_code: bool| {
+        let block = &contents[range.clone()];
+        let transformed = if !is_code {
+            Some(transform_prose(contents, range.clone(), &references))
+        } else if block.contains("{{#") {
+            None
+        } else {
+            Some(transform_code(contents, range.clone()))
+        };
+        if let Some(transformed) = &transformed {
+            if ONLY_HTML.is_match(&transformed.text) {
+                return;
+            }
+        }
+        sources.push(Source {
+            file,
+            line: contents[..range.start].matches('\n').count() + 1,
+            is_code,
+            transformed,
+        });
+    };
+
+    let mut current: Option<(usize, bool)> = None;
+    let mut offset = 0;
+    for line in contents.split_inclusive('\n') {
+        let (start, end) = (offset, offset + line.trim_end_matches('\n').len());
+        offset += line.len();
+        let is_fence = line.trim_start().starts_with("```");
+        match current {
+            Some((paragraph_start, true)) => {
+                if is_fence {
+                    push(paragraph_start..end, true);
+                    current = None;
+                }
+            }
+            _ if is_fence => {
+                if let Some((paragraph_start, _)) = current {
+                    push(paragraph_start..start, false);
+                }
+                current = Some((start, true));
+            }
+            Some((paragraph_start, false)) if is_blank(line) => {
+                push(paragraph_start..start, false);
+                current = None;
+            }
+            None if !is_blank(line) => current = Some((start, false)),
+            _ => {}
+        }
+    }
+    if let Some((paragraph_start, is_code)) = current {
+        push(paragraph_start..contents.len(), is_code);
+    }
+    sources
+}
+
+// Like `remove_markup` and `remove_hidden_lines` do
+fn transform_code(contents: &str, range: Range<usize>) -> Transformed {
+    let mut transformed = Transformed::default();
+    let mut offset = range.start;
+    for line in contents[range].split_inclusive('\n') {
+        let (start, end) = (offset, offset + line.len());
+        offset = end;
+        let content_end = start + line.trim_end_matches('\n').len();
+        if line.trim_start().starts_with("```") {
+            transformed.push(start..content_end, "```", false);
+            transformed.keep(contents, content_end..end);
+        } else if line.starts_with("# ") || line.trim_end() == "#" {
+            transformed.push(start..end, "", false);
+        } else {
+            transformed.keep(contents, start..end);
+        }
+    }
+    transformed
+}
+
+// Like `remove_links`, `link2print` and `remove_markup` do, and how the
+// manuscript writes lists and block quotes
+fn transform_prose(
+    contents: &str,
+    range: Range<usize>,
+    references: &HashMap<String, String>,
+) -> Transformed {
+    let mut transformed = Transformed::default();
+    let is_quote = contents[range.clone()].starts_with('>');
+    let mut offset = range.start;
+    let mut in_definition = false;
+    for (index, line) in contents[range].split_inclusive('\n').enumerate() {
+        let (start, end) = (offset, offset + line.len());
+        offset = end;
+        let content = line.trim_end_matches('\n');
+        // The URL of a definition can be on the next line
+        if in_definition {
+            transformed.push(start..end, "", false);
+            in_definition = false;
+            continue;
+        }
+        if let Some(caps) = REFERENCE_DEFINITION.captures(content) {
+            transformed.push(start..end, "", false);
+            in_definition = caps[2].trim().is_empty();
+            continue;
+        }
+
+        let mut position = start;
+        if is_quote && index > 0 && content.starts_with('>') {
+            position += if content.starts_with("> ") { 2 } else { 1 };
+            transformed.push(start..position, "", false);
+        }
+        let line_end = start + content.len();
+        if let Some(caps) = LIST_MARKER.captures(&contents[position..line_end])
+        {
+            let marker = if caps[2].starts_with(char::is_numeric) {
+                "1. "
+            } else if caps[1].is_empty() {
+                "* "
+            } else {
+                "  * "
+            };
+            transformed.push(position..position + caps[0].len(), marker, false);
+            position += caps[0].len();
+        }
+        if let Some(span) = SPAN_START.find(&contents[position..]) {
+            transformed.push(position..position + span.end(), "", false);
+            position += span.end();
+        }
+        let mut content_end = start + content.len();
+        let span_end = content.ends_with("</span>");
+        if span_end {
+            content_end -= "</span>".len();
+        }
+
+        transform_inline(
+            &mut transformed,
+            contents,
+            position..content_end,
+            references,
+        );
+        if span_end {
+            transformed.push(content_end..content_end + 7, "", false);
+        }
+        transformed.keep(contents, start + content.len()..end);
+    }
+    transformed
+}
+
+fn transform_inline(
+    transformed: &mut Transformed,
+    contents: &str,
+    range: Range<usize>,
+    references: &HashMap<String, String>,
+) {
+    let mut position = range.start;
+    for caps in INLINE.captures_iter(&contents[range.clone()]) {
+        let whole = caps.get(0).unwrap();
+        let name = match caps.name("name") {
+            Some(name) => name,
+            None => continue,
+        };
+        // `remove_links` only removes links with a URL or a reference
+        let has_target =
+            caps.name("url").is_some() || caps.name("key").is_some();
+        let ignored = has_target && caps.name("ignore").is_some();
+        let whole_end = match caps.name("ignore") {
+            Some(ignore) if !ignored => ignore.start(),
+            _ => whole.end(),
+        };
+        let (start, end) =
+            (range.start + whole.start(), range.start + whole_end);
+        let url = if ignored {
+            None
+        } else if let Some(url) = caps.name("url") {
+            Some(url.as_str().to_string())
+        } else {
+            let key = match caps.name("key") {
+                Some(key) if !key.as_str().is_empty() => key.as_str(),
+                Some(_) => name.as_str(),
+                None if NOT_A_LINK.is_match(name.as_str()) => {
+                    transformed.keep(contents, position..start);
+                    transformed.push(start..start + 1, "", false);
+                    transformed.keep(contents, start + 1..end - 1);
+                    transformed.push(end - 1..end, "", false);
+                    position = end;
+                    continue;
+                }
+                None => name.as_str(),
+            };
+            match references.get(&key.to_uppercase()) {
+                Some(url) => Some(url.clone()),
+                None => continue,
+            }
+        };
+
+        let name_start = range.start + name.start();
+        let name_end = range.start + name.end();
+        transformed.keep(contents, position..start);
+        transformed.push(start..name_start, "", false);
+        transformed.keep(contents, name_start..name_end);
+        let rest = url.map_or_else(String::new, |url| format!(" at *{}*", url));
+        transformed.push(name_end..end, &rest, false);
+        position = end;
+    }
+    transformed.keep(contents, position..range.end);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pairing;
+
+    #[test]
+    fn edits_map_back_through_links_but_not_into_them() {
+        let contents = "請見[官方網站](https://www.rust-lang.org)上的說明。\n";
+        let sources = super::source_paragraphs(0, contents);
+        let transformed = sources[0].transformed.as_ref().unwrap();
+        assert_eq!(
+            transformed.text,
+            "請見官方網站 at *https://www.rust-lang.org*上的說明。\n"
+        );
+
+        let edit = transformed.text.find("網站").unwrap();
+        assert_eq!(
+            transformed.original_range(&(edit..edit + "網站".len())),
+            Some(contents.find("網站").unwrap()..contents.find("](").unwrap())
+        );
+        let url = transformed.text.find("rust-lang").unwrap();
+        assert_eq!(transformed.original_range(&(url..url + 4)), None);
+    }
+
+    #[test]
+    fn insertions_next_to_links_are_left_for_review() {
+        let contents = "請見[資料型別](ch03-02-data-types.md)。\n";
+        let references = Default::default();
+        let mut transformed = super::Transformed::default();
+        super::transform_inline(
+            &mut transformed,
+            contents,
+            0..contents.len(),
+            &references,
+        );
+
+        let link = transformed.text.find("資料型別").unwrap();
+        assert_eq!(transformed.original_range(&(link..link)), None);
+        let end = link + "資料型別".len();
+        assert_eq!(transformed.original_range(&(end..end)), None);
+        let before = transformed.text.find('見').unwrap();
+        assert_eq!(
+            transformed.original_range(&(before..before)),
+            Some(before..before)
+        );
+    }
+
+    #[test]
+    fn manuscript_lines_are_unwrapped() {
+        let paragraphs = super::manuscript_paragraphs(
+            "\n[TOC]\n\n# 猜謎遊戲\n\n我們來寫一個\n猜謎遊戲，使用 Rust and\nCargo to build it.\n\n* 一\n* 二\n\n```\nfn main() {\n\n}\n```\n",
+        );
+
+        let texts = paragraphs
+            .iter()
+            .map(|paragraph| (paragraph.line, paragraph.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            texts,
+            vec![
+                (4, "# 猜謎遊戲"),
+                (6, "我們來寫一個猜謎遊戲，使用 Rust and Cargo to build it."),
+                (10, "* 一\n* 二"),
+                (13, "```\nfn main() {\n\n}\n```"),
+            ]
+        );
+    }
+
+    #[test]
+    fn pair_up_keeps_similar_paragraphs_together() {
+        let keys = |keys: &[&str]| {
+            keys.iter().map(|key| key.to_string()).collect::<Vec<_>>()
+        };
+        let old = keys(&["一二三四五", "六七八九十"]);
+        let new = keys(&["全新的段落", "一二三四五六", "六七八九"]);
+
+        assert_eq!(
+            super::pair_up(0..2, 0..3, &old, &new),
+            vec![
+                Pairing::Manuscript(0),
+                Pairing::Both((0, 1)),
+                Pairing::Both((1, 2)),
+            ]
+        );
+    }
+
+    #[test]
+    fn compare_patches_edits_and_lists_the_rest() {
+        let contents = "## 變數\n\n變數預設是不可變的。\n[連結][ref]很重要。\n\n```rust\n# fn hidden() {}\nlet x = 5;\n```\n\n刪掉的段落。\n\n[ref]: https://example.com\n";
+        let manuscript = "\n[TOC]\n\n## 變數\n\n變數預設是*不可*變的。\n鏈結 at *https://example.com*很重要。\n\n```\nlet x = 5;\n```\n\n";
+
+        let comparison = super::compare(
+            &[("src/ch03-01.md", contents)],
+            "nostarch/chapter03.md",
+            manuscript,
+        );
+
+        assert_eq!(
+            comparison.patch,
+            "--- a/src/ch03-01.md\n+++ b/src/ch03-01.md\n@@ -1,7 +1,7 @@\n ## 變數\n \n-變數預設是不可變的。\n-[連結][ref]很重要。\n+變數預設是*不可*變的。\n+[鏈結][ref]很重要。\n \n ```rust\n # fn hidden() {}\n"
+        );
+        assert_eq!(comparison.edits, 3);
+        assert_eq!(
+            comparison.review,
+            vec!["src/ch03-01.md:11: not in the manuscript".to_string()]
+        );
+    }
+}
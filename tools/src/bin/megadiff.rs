@@ -0,0 +1,480 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use similar::{capture_diff_slices, Algorithm, DiffTag, TextDiff};
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    // Default to the directories `update-rustc.sh` builds the book into
+    let (before_dir, after_dir) = if args.arg_before_dir.is_empty() {
+        (Path::new("tmp/book-before"), Path::new("tmp/book-after"))
+    } else {
+        (
+            Path::new(&args.arg_before_dir),
+            Path::new(&args.arg_after_dir),
+        )
+    };
+
+    let mut pages = pages_of_interest(before_dir)?;
+    pages.extend(pages_of_interest(after_dir)?);
+
+    let mut page_diffs = Vec::new();
+    for page in pages {
+        let before = read_main_content(&before_dir.join(&page))?;
+        let after = read_main_content(&after_dir.join(&page))?;
+        if before != after {
+            page_diffs.push(diff_page(page, before, after));
+        }
+    }
+
+    let report = match args.flag_format {
+        Format::Html => render_html(&page_diffs),
+        Format::Md => render_markdown(&page_diffs),
+    };
+
+    let output = args.flag_output.unwrap_or_else(|| match args.flag_format {
+        Format::Html => String::from("tmp/megadiff.html"),
+        Format::Md => String::from("tmp/megadiff.md"),
+    });
+    fs::write(&output, report)?;
+
+    println!("{} changed page(s), report in {}", page_diffs.len(), output);
+
+    Ok(())
+}
+
+const USAGE: &str = "
+megadiff
+Compares two builds of the book and reports every page whose content changed.
+
+Usage:
+  megadiff [options] [<before-dir> <after-dir>]
+  megadiff (-h | --help)
+
+The directories default to `tmp/book-before` and `tmp/book-after`.
+
+Options:
+  -h --help          Show this screen.
+  --format=<format>  Report format, `html` or `md` [default: html].
+  --output=<file>    Where to write the report, defaults to
+                     `tmp/megadiff.html` or `tmp/megadiff.md`.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_before_dir: String,
+    arg_after_dir: String,
+    flag_format: Format,
+    flag_output: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Format {
+    Html,
+    Md,
+}
+
+// Directories and files that are never affected by rustfmt or are otherwise
+// uninteresting; everything else that isn't an HTML page is skipped as well.
+const IGNORED_DIRS: &[&str] = &["css", "theme", "img", "FontAwesome"];
+const IGNORED_FILES: &[&str] = &["print.html"];
+
+fn is_page_of_interest(relative_path: &Path) -> bool {
+    let is_in_ignored_dir = relative_path.components().count() > 1
+        && relative_path.components().next().map_or(false, |first| {
+            IGNORED_DIRS.iter().any(|dir| first.as_os_str() == *dir)
+        });
+
+    !is_in_ignored_dir
+        && relative_path.extension().map_or(false, |ext| ext == "html")
+        && !IGNORED_FILES
+            .iter()
+            .any(|file| relative_path == Path::new(file))
+}
+
+fn pages_of_interest(dir: &Path) -> Result<BTreeSet<PathBuf>, Box<dyn Error>> {
+    let mut pages = BTreeSet::new();
+    if !dir.is_dir() {
+        return Err(format!("{} is not a directory", dir.display()).into());
+    }
+    for entry in walkdir::WalkDir::new(dir).min_depth(1) {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(dir)?;
+        if entry.file_type().is_file() && is_page_of_interest(relative_path) {
+            pages.insert(relative_path.to_owned());
+        }
+    }
+    Ok(pages)
+}
+
+// Pages that only exist on one side are compared against an empty page.
+fn read_main_content(path: &Path) -> Result<String, Box<dyn Error>> {
+    if !path.exists() {
+        return Ok(String::new());
+    }
+    Ok(main_content(&fs::read_to_string(path)?).to_string())
+}
+
+lazy_static! {
+    static ref MAIN: Regex = Regex::new(r"(?s)<main>(.*)</main>").unwrap();
+    static ref CODE_BLOCK: Regex =
+        Regex::new(r"(?s)<pre[^>]*>(.*?)</pre>").unwrap();
+    static ref TAG: Regex = Regex::new(r"<[^>]*>").unwrap();
+}
+
+// Only the `<main>` element holds the chapter; everything around it is the
+// sidebar, menus and scripts that change for reasons we don't care about.
+fn main_content(html: &str) -> &str {
+    MAIN.captures(html)
+        .and_then(|caps| caps.get(1))
+        .map_or(html, |content| content.as_str())
+        .trim()
+}
+
+fn code_blocks(main: &str) -> Vec<String> {
+    CODE_BLOCK
+        .captures_iter(main)
+        .map(|caps| unescape_html(&TAG.replace_all(&caps[1], "")))
+        .collect()
+}
+
+fn prose(main: &str) -> String {
+    let without_code = CODE_BLOCK.replace_all(main, "");
+    TAG.replace_all(&without_code, "")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(unescape_html)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+struct PageDiff {
+    page: PathBuf,
+    code_blocks: Vec<(String, Vec<Row>)>,
+    prose: Vec<Row>,
+}
+
+// One row of a side-by-side diff; `None` means the line doesn't exist on that
+// side.
+#[derive(Debug, PartialEq)]
+struct Row {
+    tag: DiffTag,
+    before: Option<String>,
+    after: Option<String>,
+}
+
+fn diff_page(page: PathBuf, before: String, after: String) -> PageDiff {
+    let before_blocks = code_blocks(&before);
+    let after_blocks = code_blocks(&after);
+
+    // Blocks are aligned like lines, so a block that was added or removed
+    // doesn't make every block after it look changed
+    let mut code_blocks = Vec::new();
+    let ops =
+        capture_diff_slices(Algorithm::Lcs, &before_blocks, &after_blocks);
+    for op in ops {
+        let (tag, before_range, after_range) = op.as_tag_tuple();
+        if tag == DiffTag::Equal {
+            continue;
+        }
+        let len = before_range.len().max(after_range.len());
+        for offset in 0..len {
+            let index = |range: &std::ops::Range<usize>| {
+                Some(range.start + offset).filter(|_| offset < range.len())
+            };
+            let (before_index, after_index) =
+                (index(&before_range), index(&after_range));
+            let before_block = before_index.map_or("", |i| &before_blocks[i]);
+            let after_block = after_index.map_or("", |i| &after_blocks[i]);
+            code_blocks.push((
+                block_label(before_index, after_index),
+                side_by_side(before_block, after_block),
+            ));
+        }
+    }
+
+    let before_prose = prose(&before);
+    let after_prose = prose(&after);
+    let prose = if before_prose == after_prose {
+        vec![]
+    } else {
+        side_by_side(&before_prose, &after_prose)
+    };
+
+    PageDiff {
+        page,
+        code_blocks,
+        prose,
+    }
+}
+
+// Blocks are numbered from one on each side
+fn block_label(before: Option<usize>, after: Option<usize>) -> String {
+    match (before, after) {
+        (Some(before), Some(after)) if before == after => {
+            format!("Code block {}", before + 1)
+        }
+        (Some(before), Some(after)) => {
+            format!("Code block {} → {}", before + 1, after + 1)
+        }
+        (Some(before), None) => format!("Code block {}, removed", before + 1),
+        (None, Some(after)) => format!("Code block {}, added", after + 1),
+        (None, None) => String::from("Code block"),
+    }
+}
+
+fn side_by_side(before: &str, after: &str) -> Vec<Row> {
+    let diff = TextDiff::from_lines(before, after);
+    let before_lines: Vec<_> = before.lines().collect();
+    let after_lines: Vec<_> = after.lines().collect();
+
+    let mut rows = Vec::new();
+    for op in diff.ops() {
+        let (tag, before_range, after_range) = op.as_tag_tuple();
+        let len = before_range.len().max(after_range.len());
+        for offset in 0..len {
+            let line = |lines: &[&str], range: &std::ops::Range<usize>| {
+                if offset < range.len() {
+                    lines.get(range.start + offset).map(|l| l.to_string())
+                } else {
+                    None
+                }
+            };
+            rows.push(Row {
+                tag,
+                before: line(&before_lines, &before_range),
+                after: line(&after_lines, &after_range),
+            });
+        }
+    }
+    rows
+}
+
+fn render_html(page_diffs: &[PageDiff]) -> String {
+    let mut html = String::from(
+        r#"<!DOCTYPE html>
+<html lang="zh-TW">
+<head>
+<meta charset="utf-8">
+<title>megadiff</title>
+<style>
+body { font-family: sans-serif; }
+table { border-collapse: collapse; width: 100%; table-layout: fixed; }
+td { font-family: monospace; white-space: pre-wrap; vertical-align: top; padding: 0 0.5em; }
+.delete { background: #fdd; }
+.insert { background: #dfd; }
+.replace { background: #ffd; }
+</style>
+</head>
+<body>
+"#,
+    );
+
+    writeln!(html, "<h1>{} changed page(s)</h1>", page_diffs.len()).unwrap();
+    for page_diff in page_diffs {
+        let page = escape_html(&page_diff.page.to_string_lossy());
+        writeln!(html, "<h2>{}</h2>", page).unwrap();
+        for (label, rows) in &page_diff.code_blocks {
+            writeln!(html, "<h3>{}</h3>", label).unwrap();
+            render_html_table(&mut html, rows);
+        }
+        if !page_diff.prose.is_empty() {
+            writeln!(html, "<h3>Text</h3>").unwrap();
+            render_html_table(&mut html, &page_diff.prose);
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn render_html_table(html: &mut String, rows: &[Row]) {
+    html.push_str("<table>\n<tr><th>before</th><th>after</th></tr>\n");
+    for row in rows {
+        let class = match row.tag {
+            DiffTag::Equal => "equal",
+            DiffTag::Delete => "delete",
+            DiffTag::Insert => "insert",
+            DiffTag::Replace => "replace",
+        };
+        writeln!(
+            html,
+            r#"<tr class="{}"><td>{}</td><td>{}</td></tr>"#,
+            class,
+            escape_html(row.before.as_deref().unwrap_or("")),
+            escape_html(row.after.as_deref().unwrap_or("")),
+        )
+        .unwrap();
+    }
+    html.push_str("</table>\n");
+}
+
+fn render_markdown(page_diffs: &[PageDiff]) -> String {
+    let mut md = String::new();
+
+    writeln!(md, "# {} changed page(s)", page_diffs.len()).unwrap();
+    for page_diff in page_diffs {
+        writeln!(md, "\n## {}", page_diff.page.to_string_lossy()).unwrap();
+        for (label, rows) in &page_diff.code_blocks {
+            writeln!(md, "\n### {}\n", label).unwrap();
+            render_markdown_table(&mut md, rows);
+        }
+        if !page_diff.prose.is_empty() {
+            writeln!(md, "\n### Text\n").unwrap();
+            render_markdown_table(&mut md, &page_diff.prose);
+        }
+    }
+    md
+}
+
+fn render_markdown_table(md: &mut String, rows: &[Row]) {
+    md.push_str("| | before | after |\n| - | - | - |\n");
+    for row in rows {
+        let marker = match row.tag {
+            DiffTag::Equal => " ",
+            DiffTag::Delete => "-",
+            DiffTag::Insert => "+",
+            DiffTag::Replace => "~",
+        };
+        let cell = |line: &Option<String>| {
+            line.as_deref()
+                .map(|line| {
+                    format!("<code>{}</code>", escape_html(line))
+                        .replace('|', "&#124;")
+                })
+                .unwrap_or_default()
+        };
+        writeln!(
+            md,
+            "| {} | {} | {} |",
+            marker,
+            cell(&row.before),
+            cell(&row.after)
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use similar::DiffTag;
+    use std::path::Path;
+
+    #[test]
+    fn is_page_of_interest_skips_assets_and_print_html() {
+        assert!(super::is_page_of_interest(Path::new(
+            "ch01-01-installation.html"
+        )));
+        assert!(!super::is_page_of_interest(Path::new("print.html")));
+        assert!(!super::is_page_of_interest(Path::new("searchindex.json")));
+        assert!(!super::is_page_of_interest(Path::new("theme/index.html")));
+        assert!(!super::is_page_of_interest(Path::new(
+            "FontAwesome/fonts/fontawesome-webfont.svg"
+        )));
+    }
+
+    #[test]
+    fn main_content_extracts_only_the_main_element() {
+        let html = r#"<nav>目錄</nav>
+<main>
+<h1>Hello, World!</h1>
+</main>
+<script src="book.js"></script>"#;
+
+        assert_eq!(super::main_content(html), "<h1>Hello, World!</h1>");
+    }
+
+    #[test]
+    fn code_blocks_strips_highlighting_and_unescapes() {
+        let main = r#"<p>範例：</p>
+<pre><code class="language-rust"><span class="boring">fn main() {
+</span>    let v: Vec&lt;i32&gt; = Vec::new();
+<span class="boring">}</span></code></pre>"#;
+
+        assert_eq!(
+            super::code_blocks(main),
+            vec!["fn main() {\n    let v: Vec<i32> = Vec::new();\n}"]
+        );
+    }
+
+    #[test]
+    fn diff_page_aligns_code_blocks() {
+        let before = "<pre><code>a</code></pre>\n<pre><code>b</code></pre>";
+        let after = "<pre><code>new</code></pre>\n<pre><code>a</code></pre>\n\
+                     <pre><code>B</code></pre>";
+
+        let diff = super::diff_page(
+            Path::new("ch01.html").to_path_buf(),
+            before.to_string(),
+            after.to_string(),
+        );
+
+        let labels = diff
+            .code_blocks
+            .iter()
+            .map(|(label, _)| label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(labels, ["Code block 1, added", "Code block 2 → 3"]);
+        assert!(diff.prose.is_empty());
+    }
+
+    #[test]
+    fn side_by_side_pairs_replaced_lines() {
+        let rows = super::side_by_side("a\nb\nc\n", "a\nB\nc\nd\n");
+
+        assert_eq!(
+            rows,
+            vec![
+                super::Row {
+                    tag: DiffTag::Equal,
+                    before: Some("a".to_string()),
+                    after: Some("a".to_string()),
+                },
+                super::Row {
+                    tag: DiffTag::Replace,
+                    before: Some("b".to_string()),
+                    after: Some("B".to_string()),
+                },
+                super::Row {
+                    tag: DiffTag::Equal,
+                    before: Some("c".to_string()),
+                    after: Some("c".to_string()),
+                },
+                super::Row {
+                    tag: DiffTag::Insert,
+                    before: None,
+                    after: Some("d".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,290 @@
+#[macro_use]
+extern crate lazy_static;
+
+use docopt::Docopt;
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let src_dir = Path::new(&args.arg_src_dir);
+    let listings_dir = Path::new(&args.arg_listings_dir);
+
+    let mut references = References::default();
+    let mut chapters = fs::read_dir(src_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?;
+    chapters.sort();
+    for chapter in chapters {
+        if chapter.extension().map_or(false, |ext| ext == "md") {
+            references.add_chapter(&fs::read_to_string(chapter)?);
+        }
+    }
+
+    let orphans = find_orphans(listings_dir, &references)?;
+
+    print_orphans("Listings no chapter uses:", &orphans.listings);
+    print_orphans("Output no chapter includes:", &orphans.outputs);
+    print_orphans("Files that nothing includes or mentions:", &orphans.files);
+
+    // A typo in a path makes a listing look unused, so show those too
+    let dangling = references
+        .files
+        .iter()
+        .filter(|file| !listings_dir.join(file).exists())
+        .cloned()
+        .collect::<Vec<_>>();
+    print_orphans("References to paths that don't exist:", &dangling);
+
+    if let Some(script_path) = &args.flag_script {
+        fs::write(script_path, deletion_script(listings_dir, &orphans))?;
+        println!("\nDeletion script in {}", script_path);
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "
+orphan_listings
+Finds listings, output and other files that no chapter references anymore.
+It only reports them; nothing is deleted unless you run the --script.
+
+Usage:
+  orphan_listings [options] <src-dir> <listings-dir>
+  orphan_listings (-h | --help)
+
+Options:
+  -h --help         Show this screen.
+  --script=<file>   Also write a shell script that deletes the orphans.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_src_dir: String,
+    arg_listings_dir: String,
+    flag_script: Option<String>,
+}
+
+lazy_static! {
+    static ref LISTING_INCLUDE: Regex =
+        Regex::new(r"\{\{#(?:rustdoc_)?include\s+\.\./listings/([^}:\s]+)")
+            .unwrap();
+    static ref LISTING_PATH: Regex =
+        Regex::new(r"(?m)(?:^|\s)listings/([^\s]+)").unwrap();
+}
+
+// Everything in the listings directory the chapters point at, relative to the
+// listings directory.
+#[derive(Debug, Default)]
+struct References {
+    // Files included with `{{#include}}` or `{{#rustdoc_include}}`, or named
+    // in the commands of a `manual-regeneration` comment
+    files: BTreeSet<PathBuf>,
+    // Listings with referenced files, or whose output is regenerated by hand
+    listings: BTreeSet<PathBuf>,
+    // Everything the chapters say, to find the files they only mention
+    text: String,
+}
+
+impl References {
+    fn add_chapter(&mut self, contents: &str) {
+        let paths = LISTING_INCLUDE
+            .captures_iter(contents)
+            .chain(LISTING_PATH.captures_iter(contents));
+        for caps in paths {
+            let file = PathBuf::from(&caps[1]);
+            self.listings.insert(file.components().take(2).collect());
+            self.files.insert(file);
+        }
+        self.text.push_str(contents);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Orphans {
+    listings: Vec<PathBuf>,
+    outputs: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+// A listing nothing points at is an orphan as a whole. Within the listings
+// that are used, `output.txt` files have to be referenced, and other files
+// that aren't part of a crate have to be referenced or at least mentioned by
+// the code or the chapters, like the `poem.txt` that `minigrep` reads.
+fn find_orphans(
+    listings_dir: &Path,
+    references: &References,
+) -> Result<Orphans, Box<dyn Error>> {
+    let mut orphans = Orphans::default();
+
+    for entry in walkdir::WalkDir::new(listings_dir)
+        .min_depth(2)
+        .max_depth(2)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    {
+        let entry = entry?;
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        let listing = entry.path().strip_prefix(listings_dir)?;
+        if !references.listings.contains(listing) {
+            orphans.listings.push(listing.to_owned());
+            continue;
+        }
+
+        let mut files = Vec::new();
+        let walker = walkdir::WalkDir::new(entry.path())
+            .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != "target");
+        for file in walker {
+            let file = file?;
+            if file.file_type().is_file() {
+                files.push(file.path().strip_prefix(listings_dir)?.to_owned());
+            }
+        }
+
+        let code = files
+            .iter()
+            .filter(|file| is_crate_file(file))
+            .map(|file| fs::read_to_string(listings_dir.join(file)))
+            .collect::<Result<Vec<_>, _>>()?
+            .join("\n");
+
+        for file in files {
+            if references.files.contains(&file) || is_crate_file(&file) {
+                continue;
+            }
+            let file_name = file
+                .file_name()
+                .expect("File should've had a name")
+                .to_string_lossy();
+            if file_name == "output.txt" {
+                orphans.outputs.push(file);
+            } else if !is_tool_file(&file_name)
+                && !code.contains(&*file_name)
+                && !references.text.contains(&*file_name)
+            {
+                orphans.files.push(file);
+            }
+        }
+    }
+
+    Ok(orphans)
+}
+
+// Files that make up a crate are used by building it, whether or not the book
+// shows them.
+fn is_crate_file(file: &Path) -> bool {
+    let file_name = file.file_name().and_then(|name| name.to_str());
+    matches!(file_name, Some("Cargo.toml" | "Cargo.lock"))
+        || file.extension().map_or(false, |ext| ext == "rs")
+}
+
+// Files for git and our tools rather than for readers
+fn is_tool_file(file_name: &str) -> bool {
+    matches!(
+        file_name,
+        ".gitignore"
+            | ".rustfmt.toml"
+            | "rustfmt.toml"
+            | "rustfmt-ignore"
+            | "does-not-compile"
+    )
+}
+
+fn deletion_script(listings_dir: &Path, orphans: &Orphans) -> String {
+    let mut script = String::from(
+        "#!/bin/bash\n\
+         # Generated by `orphan_listings`: deletes the listings and files that\n\
+         # no chapter references. Check the list before running it.\n\
+         set -eu\n",
+    );
+    let groups = [
+        ("Listings", &orphans.listings),
+        ("Output", &orphans.outputs),
+        ("Files", &orphans.files),
+    ];
+    for (title, paths) in groups {
+        if paths.is_empty() {
+            continue;
+        }
+        script.push_str(&format!("\n# {}\n", title));
+        for path in paths {
+            script.push_str(&format!(
+                "git rm -r --quiet '{}'\n",
+                listings_dir.join(path).display()
+            ));
+        }
+    }
+    script
+}
+
+fn print_orphans(title: &str, paths: &[PathBuf]) {
+    if paths.is_empty() {
+        return;
+    }
+    println!("{}", title);
+    for path in paths {
+        println!("  {}", path.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn references_include_files_and_manually_regenerated_listings() {
+        let mut references = super::References::default();
+        references.add_chapter(
+            r#"```rust
+{{#rustdoc_include ../listings/ch12-an-io-project/listing-12-14/src/main.rs:here}}
+```
+
+<!-- manual-regeneration
+cd listings/ch02-guessing-game-tutorial/listing-02-02/
+cargo build -->
+"#,
+        );
+
+        assert!(references.files.contains(Path::new(
+            "ch12-an-io-project/listing-12-14/src/main.rs"
+        )));
+        assert_eq!(
+            references.listings.into_iter().collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("ch02-guessing-game-tutorial/listing-02-02"),
+                PathBuf::from("ch12-an-io-project/listing-12-14"),
+            ]
+        );
+    }
+
+    #[test]
+    fn deletion_script_removes_every_orphan() {
+        let orphans = super::Orphans {
+            listings: vec![PathBuf::from("ch05-structs/listing-05-99")],
+            outputs: vec![PathBuf::from(
+                "ch05-structs/listing-05-01/output.txt",
+            )],
+            files: Vec::new(),
+        };
+
+        let script = super::deletion_script(Path::new("listings"), &orphans);
+
+        assert!(script.contains(
+            "# Listings\ngit rm -r --quiet 'listings/ch05-structs/listing-05-99'\n"
+        ));
+        assert!(script.contains(
+            "# Output\ngit rm -r --quiet 'listings/ch05-structs/listing-05-01/output.txt'\n"
+        ));
+        assert!(!script.contains("# Files"));
+    }
+}
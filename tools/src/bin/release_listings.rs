@@ -1,39 +1,92 @@
 #[macro_use]
 extern crate lazy_static;
 
-use regex::Regex;
+use docopt::Docopt;
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
 use std::error::Error;
+use std::ffi::OsStr;
 use std::fs;
 use std::fs::File;
+use std::io;
 use std::io::prelude::*;
 use std::io::{BufReader, BufWriter};
 use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml_edit::{value, Array, Document, Item, Table};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // Get all listings from the `listings` directory
-    let listings_dir = Path::new("listings");
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
 
-    // Put the results in the `tmp/listings` directory
-    let out_dir = Path::new("tmp/listings");
+    // Get all listings from the listings directory
+    let listings_dir = Path::new(&args.flag_listings_dir);
 
-    // Clear out any existing content in `tmp/listings`
-    if out_dir.is_dir() {
-        fs::remove_dir_all(out_dir)?;
+    // Put the results in the `listings` directory of the output directory
+    let output_dir = Path::new(&args.flag_output_dir);
+    fs::create_dir_all(output_dir)?;
+    let out_dir = output_listings_dir(listings_dir, output_dir)?;
+
+    // A workspace for the whole book wouldn't be in any per-chapter archive
+    if args.flag_per_chapter
+        && matches!(args.flag_workspace, Some(WorkspaceScope::Book))
+    {
+        return Err(
+            "`--workspace=book` can't be used with `--per-chapter`".into()
+        );
     }
 
-    // Create a new, empty `tmp/listings` directory
-    fs::create_dir(out_dir)?;
+    // Check everything that can be wrong before the last release is cleared
+    let chapter_filter = args
+        .flag_chapters
+        .as_deref()
+        .map(|chapters| chapters.split(',').map(str::trim).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let chapters = chapters_to_release(listings_dir, &chapter_filter)?;
+    let mtime = source_date_epoch()?;
+    let cleaning_rules =
+        CleaningRules::from_config(&fs::read_to_string(&args.flag_config)?)?;
 
-    // For each chapter in the `listings` directory,
-    for chapter in fs::read_dir(listings_dir)? {
-        let chapter = chapter?;
-        let chapter_path = chapter.path();
+    let src_dir = Path::new(&args.flag_src_dir);
+    let readme = Readme::new(src_dir, &args.flag_book_url)?;
+    let expected_failures = if args.flag_verify || args.flag_workspace.is_some()
+    {
+        listings_expected_to_fail(src_dir, listings_dir)?
+    } else {
+        BTreeSet::new()
+    };
+
+    // Clear out the output `listings` directory, but only when an earlier run
+    // created it
+    let marker = output_dir.join(OUTPUT_MARKER);
+    if out_dir.exists() {
+        if !marker.is_file() {
+            return Err(format!(
+                "{} wasn't created by release_listings; remove it or use \
+                 another --output-dir",
+                out_dir.display()
+            )
+            .into());
+        }
+        fs::remove_dir_all(&out_dir)?;
+    }
+
+    // Create a new, empty output `listings` directory
+    fs::create_dir_all(&out_dir)?;
+    File::create(&marker)?;
 
+    // For each chapter in the listings directory,
+    for chapter_path in &chapters {
         let chapter_name = chapter_path
             .file_name()
             .expect("Chapter should've had a name");
 
-        // Create a corresponding chapter dir in `tmp/listings`
+        // Create a corresponding chapter dir in the output directory
         let output_chapter_path = out_dir.join(chapter_name);
         fs::create_dir(&output_chapter_path)?;
 
@@ -51,32 +104,1237 @@ fn main() -> Result<(), Box<dyn Error>> {
             fs::create_dir(&output_listing_dir)?;
 
             // Copy all the cleaned files in the listing to the tmp directory
-            copy_cleaned_listing_files(listing_path, output_listing_dir)?;
+            copy_cleaned_listing_files(
+                &cleaning_rules,
+                listings_dir,
+                listing_path,
+                output_listing_dir,
+            )?;
         }
     }
 
-    // Create a compressed archive of all the listings
-    let tarfile = File::create("tmp/listings.tar.gz")?;
-    let encoder =
-        flate2::write::GzEncoder::new(tarfile, flate2::Compression::default());
-    let mut archive = tar::Builder::new(encoder);
-    archive.append_dir_all("listings", "tmp/listings")?;
+    // Map the listing directories back to the book with a `README.md` index
+    readme.write(&out_dir, &chapters, false)?;
+    if args.flag_per_chapter {
+        for chapter_path in &chapters {
+            let chapter_name = chapter_path
+                .file_name()
+                .expect("Chapter should've had a name");
+            readme.write(
+                &out_dir.join(chapter_name),
+                &[chapter_path.clone()],
+                true,
+            )?;
+        }
+    }
+
+    // Make sure the cleaning didn't break any listing that should compile
+    if args.flag_verify {
+        let regressions = verify_listings(
+            listings_dir,
+            &out_dir,
+            &output_dir.join("verify"),
+            &expected_failures,
+        )?;
+        if !regressions.is_empty() {
+            eprintln!("\nCleaning broke {} listing(s):", regressions.len());
+            for crate_dir in &regressions {
+                eprintln!("  {}", crate_dir.display());
+            }
+            std::process::exit(1);
+        }
+        println!("Every cleaned listing that should compile still does");
+    }
+
+    // Turn the listings into Cargo workspaces, either one per chapter or one
+    // with all of them
+    if let Some(scope) = args.flag_workspace {
+        let workspace_dirs = match scope {
+            WorkspaceScope::Chapter => chapters
+                .iter()
+                .map(|chapter_path| {
+                    out_dir.join(
+                        chapter_path
+                            .file_name()
+                            .expect("Chapter should've had a name"),
+                    )
+                })
+                .collect(),
+            WorkspaceScope::Book => vec![out_dir.clone()],
+        };
+        for workspace_dir in &workspace_dirs {
+            write_workspace(workspace_dir, &out_dir, &expected_failures)?;
+            println!("Cargo workspace in {}", workspace_dir.display());
+        }
+
+        // Vendor the dependencies once per archive so every workspace in it
+        // shares the same `vendor` directory
+        if args.flag_vendor {
+            let vendor_roots = if args.flag_per_chapter {
+                chapters
+                    .iter()
+                    .map(|chapter_path| {
+                        out_dir.join(
+                            chapter_path
+                                .file_name()
+                                .expect("Chapter should've had a name"),
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![out_dir.clone()]
+            };
+            for vendor_root in vendor_roots {
+                let workspaces: Vec<&PathBuf> = workspace_dirs
+                    .iter()
+                    .filter(|dir| dir.starts_with(&vendor_root))
+                    .collect();
+                vendor_dependencies(
+                    &vendor_root,
+                    &workspaces,
+                    &out_dir,
+                    &expected_failures,
+                )?;
+                println!(
+                    "Vendored dependencies in {}",
+                    vendor_root.join("vendor").display()
+                );
+            }
+        }
+    }
+
+    // Create compressed archives of the listings, either one per chapter or
+    // one with all of them
+    let mut archives = Vec::new();
+    if args.flag_per_chapter {
+        for chapter_path in &chapters {
+            let chapter_name = chapter_path
+                .file_name()
+                .expect("Chapter should've had a name")
+                .to_string_lossy();
+            let archive_path = output_dir.join(format!(
+                "{}.{}",
+                chapter_name,
+                args.flag_format.extension()
+            ));
+            write_archive(
+                args.flag_format,
+                &archive_path,
+                &chapter_name,
+                &out_dir.join(&*chapter_name),
+                mtime,
+            )?;
+            archives.push(archive_path);
+        }
+    } else {
+        let archive_path = output_dir
+            .join(format!("listings.{}", args.flag_format.extension()));
+        write_archive(
+            args.flag_format,
+            &archive_path,
+            "listings",
+            &out_dir,
+            mtime,
+        )?;
+        archives.push(archive_path);
+    }
 
     // Assure whoever is running this that the script exiting successfully, and remind them
-    // where the generated file ends up
-    println!("Release tarball of listings in tmp/listings.tar.gz");
+    // where the generated files end up
+    for archive in &archives {
+        println!("Release archive of listings in {}", archive.display());
+    }
+
+    let checksums = write_checksums(output_dir, &archives)?;
+    println!(
+        "SHA-256 checksums of the archives in {}",
+        checksums.display()
+    );
+
+    Ok(())
+}
+
+const USAGE: &str = "
+release_listings
+Copies the listings without anchor and snip comments, and archives them.
+
+Usage:
+  release_listings [options]
+  release_listings (-h | --help)
+
+Options:
+  -h --help              Show this screen.
+  --listings-dir=<dir>   Where the listings are [default: listings].
+  --output-dir=<dir>     Where to put the cleaned `listings` directory and
+                         the archives [default: tmp]. The `listings`
+                         directory is only replaced when an earlier run
+                         created it.
+  --format=<format>      Archive format, `tar.gz` or `zip` [default: tar.gz].
+  --chapters=<list>      Comma-separated chapters to release, such as
+                         `ch12,ch20`; defaults to all of them.
+  --per-chapter          Write one archive per chapter instead of a single
+                         `listings` archive.
+  --workspace=<scope>    Also generate a Cargo workspace per `chapter` or for
+                         the whole `book`.
+  --config=<file>        What to leave out of the released listings
+                         [default: tools/release_listings.toml].
+  --verify               Run `cargo check` on the cleaned listings that
+                         should compile and fail if the cleaning broke any.
+  --src-dir=<dir>        Where the chapters are, used for the `README.md`
+                         index and to find the listings that don't compile
+                         on purpose [default: src].
+  --book-url=<url>       Where the book is online, for the links in the
+                         `README.md` index [default: https://rust-lang.tw/book-tw/].
+  --vendor               Vendor the dependencies of the workspaces into one
+                         `vendor` directory per archive so they build
+                         offline.
+
+Archive entries get their timestamp from the `SOURCE_DATE_EPOCH` environment
+variable, or 1970-01-01 (1980-01-01 for zip) if it isn't set.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    flag_listings_dir: String,
+    flag_output_dir: String,
+    flag_format: Format,
+    flag_chapters: Option<String>,
+    flag_per_chapter: bool,
+    flag_workspace: Option<WorkspaceScope>,
+    flag_config: String,
+    flag_verify: bool,
+    flag_src_dir: String,
+    flag_book_url: String,
+    flag_vendor: bool,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum WorkspaceScope {
+    Chapter,
+    Book,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+enum Format {
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "zip")]
+    Zip,
+}
+
+impl Format {
+    fn extension(self) -> &'static str {
+        match self {
+            Format::TarGz => "tar.gz",
+            Format::Zip => "zip",
+        }
+    }
+}
+
+// Next to the output `listings` directory, so it stays out of the archives
+const OUTPUT_MARKER: &str = ".release_listings";
+
+// The `listings` directory in `output_dir`, unless it would be the listings
+// directory, or in or around it
+fn output_listings_dir(
+    listings_dir: &Path,
+    output_dir: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    let listings = listings_dir.canonicalize()?;
+    let out_dir = output_dir.canonicalize()?.join("listings");
+    let out_dir = out_dir.canonicalize().unwrap_or(out_dir);
+    if out_dir.starts_with(&listings) || listings.starts_with(&out_dir) {
+        return Err(format!(
+            "The output `listings` directory can't be in or around {}",
+            listings_dir.display()
+        )
+        .into());
+    }
+    Ok(output_dir.join("listings"))
+}
+
+// A chapter is selected by its full directory name or by the prefix before the
+// first dash, so `ch12` selects `ch12-an-io-project`. No filter means every
+// chapter.
+fn is_selected_chapter(chapter_name: &str, chapter_filter: &[&str]) -> bool {
+    chapter_filter.is_empty()
+        || chapter_filter.iter().any(|chapter| {
+            chapter_name == *chapter
+                || chapter_name
+                    .strip_prefix(chapter)
+                    .map_or(false, |rest| rest.starts_with('-'))
+        })
+}
+
+fn chapters_to_release(
+    listings_dir: &Path,
+    chapter_filter: &[&str],
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut chapters = Vec::new();
+    for chapter in fs::read_dir(listings_dir)? {
+        let chapter_path = chapter?.path();
+        let chapter_name = chapter_path
+            .file_name()
+            .expect("Chapter should've had a name")
+            .to_string_lossy();
+        if chapter_path.is_dir()
+            && is_selected_chapter(&chapter_name, chapter_filter)
+        {
+            chapters.push(chapter_path);
+        }
+    }
+    chapters.sort();
+
+    for chapter in chapter_filter {
+        let matched = chapters.iter().any(|chapter_path| {
+            let chapter_name = chapter_path
+                .file_name()
+                .expect("Chapter should've had a name")
+                .to_string_lossy();
+            is_selected_chapter(&chapter_name, &[chapter])
+        });
+        if !matched {
+            return Err(format!("No chapter matches `{}`", chapter).into());
+        }
+    }
+
+    Ok(chapters)
+}
+
+lazy_static! {
+    static ref LISTING_INCLUDE: Regex =
+        Regex::new(r"\{\{#(?:rustdoc_)?include \.\./listings/([^/]+)/([^/]+)/")
+            .unwrap();
+}
+
+lazy_static! {
+    static ref SUMMARY_ENTRY: Regex =
+        Regex::new(r"\[([^\]]+)\]\(([^)]+\.md)\)").unwrap();
+}
+
+// Where a listing shows up in the book: the first section that includes it,
+// and its caption if it has one.
+#[derive(Debug)]
+struct ListingSource {
+    section: String,
+    caption: Option<String>,
+}
+
+struct Readme {
+    // Section file name to its title in `SUMMARY.md`
+    titles: BTreeMap<String, String>,
+    // Listing path, relative to the listings directory, to where it's used
+    sources: BTreeMap<PathBuf, ListingSource>,
+    book_url: String,
+}
+
+impl Readme {
+    fn new(src_dir: &Path, book_url: &str) -> Result<Readme, Box<dyn Error>> {
+        let summary = fs::read_to_string(src_dir.join("SUMMARY.md"))?;
+        let titles = summary_titles(&summary);
+
+        let mut sources = BTreeMap::new();
+        // Section file names sort in the order of the chapters, so a listing
+        // belongs to the first section that uses it
+        for section in titles.keys() {
+            let contents = fs::read_to_string(src_dir.join(section))?;
+            for (listing, caption) in listing_captions(&contents) {
+                let source =
+                    sources.entry(listing).or_insert_with(|| ListingSource {
+                        section: section.clone(),
+                        caption: None,
+                    });
+                if source.caption.is_none() {
+                    source.caption = caption;
+                }
+            }
+        }
+
+        let mut book_url = book_url.to_owned();
+        if !book_url.ends_with('/') {
+            book_url.push('/');
+        }
+
+        Ok(Readme {
+            titles,
+            sources,
+            book_url,
+        })
+    }
+
+    fn section_link(&self, section: &str) -> String {
+        format!(
+            "[{}]({}{})",
+            self.titles[section],
+            self.book_url,
+            section.trim_end_matches(".md").to_owned() + ".html"
+        )
+    }
+
+    // Writes a `README.md` in `dir` listing every listing of `chapters`; with
+    // `in_chapter` the links are relative to the chapter directory.
+    fn write(
+        &self,
+        dir: &Path,
+        chapters: &[PathBuf],
+        in_chapter: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut readme = String::from(
+            "# Rust 程式設計語言：範例程式碼\n\
+             \n\
+             每個目錄都是書中的一個範例，下表列出每個範例的標題、\
+             使用該範例的章節以及該章節的線上網址。\n\
+             \n\
+             此檔案由 `release_listings` 產生，請勿手動修改。\n",
+        );
+
+        for chapter_path in chapters {
+            let chapter_name = chapter_path
+                .file_name()
+                .expect("Chapter should've had a name")
+                .to_string_lossy();
+            let chapter_number = chapter_name.split('-').next().unwrap_or("");
+            let chapter_section = self.titles.keys().find(|section| {
+                section.starts_with(&format!("{}-", chapter_number))
+            });
+            readme.push_str("\n## ");
+            match chapter_section {
+                Some(section) => readme.push_str(&self.section_link(section)),
+                None => readme.push_str(&chapter_name),
+            }
+            readme.push_str(&format!(
+                "\n\n目錄：`{}`\n\n| 範例 | 標題 | 章節 |\n| --- | --- | --- |\n",
+                chapter_name
+            ));
+
+            let mut listings = fs::read_dir(dir.join(if in_chapter {
+                Path::new("")
+            } else {
+                Path::new(&*chapter_name)
+            }))?
+            .map(|entry| Ok(entry?.file_name()))
+            .collect::<Result<Vec<_>, io::Error>>()?;
+            listings.sort();
+
+            for listing in listings
+                .iter()
+                .map(|listing| listing.to_string_lossy())
+                .filter(|listing| listing != "README.md")
+            {
+                let link = if in_chapter {
+                    format!("{}/", listing)
+                } else {
+                    format!("{}/{}/", chapter_name, listing)
+                };
+                let source = self
+                    .sources
+                    .get(&Path::new(&*chapter_name).join(&*listing));
+                let caption = source
+                    .and_then(|source| source.caption.as_deref())
+                    .unwrap_or("");
+                let section = source.map_or_else(
+                    || "（書中未使用）".to_owned(),
+                    |source| self.section_link(&source.section),
+                );
+                readme.push_str(&format!(
+                    "| [{}]({}) | {} | {} |\n",
+                    listing,
+                    link,
+                    caption.replace('|', "\\|"),
+                    section.replace('|', "\\|"),
+                ));
+            }
+        }
+
+        fs::write(dir.join("README.md"), readme)?;
+        Ok(())
+    }
+}
+
+// The title of every section file in `SUMMARY.md`, keyed by file name.
+fn summary_titles(summary: &str) -> BTreeMap<String, String> {
+    SUMMARY_ENTRY
+        .captures_iter(summary)
+        .map(|caps| (caps[2].to_owned(), caps[1].to_owned()))
+        .collect()
+}
+
+lazy_static! {
+    static ref MANUAL_REGENERATION: Regex =
+        Regex::new(r"^cd listings/([^/]+)/([^/\s]+)").unwrap();
+}
+
+// The listings a chapter includes, each with the caption that follows the
+// code block it's included in, if any. Output that's regenerated by hand is
+// pasted in the code block after the `manual-regeneration` comment saying
+// which listing it comes from.
+fn listing_captions(contents: &str) -> Vec<(PathBuf, Option<String>)> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut listings = Vec::new();
+    let mut in_code_block = false;
+    let mut block_listings = Vec::new();
+
+    for (i, line) in lines.iter().enumerate() {
+        let references = LISTING_INCLUDE
+            .captures_iter(line)
+            .chain(MANUAL_REGENERATION.captures_iter(line));
+        for caps in references {
+            let listing = Path::new(&caps[1]).join(&caps[2]);
+            if !block_listings.contains(&listing) {
+                block_listings.push(listing);
+            }
+        }
+
+        if !line.trim_start().starts_with("```") {
+            continue;
+        }
+        in_code_block = !in_code_block;
+        if !in_code_block {
+            let caption = caption_after(&lines[i + 1..]);
+            for listing in block_listings.drain(..) {
+                listings.push((listing, caption.clone()));
+            }
+        }
+    }
+
+    listings
+}
+
+fn caption_after(lines: &[&str]) -> Option<String> {
+    let mut lines = lines.iter().skip_while(|line| line.trim().is_empty());
+    let first = lines.next()?.trim();
+    let mut caption =
+        first.strip_prefix(r#"<span class="caption">"#)?.to_owned();
+    while !caption.contains("</span>") {
+        caption.push(' ');
+        caption.push_str(lines.next()?.trim());
+    }
+    let caption = caption.split("</span>").next().unwrap_or("");
+    Some(caption.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+// Listings that don't compile on purpose are the ones the chapters show in a
+// `does_not_compile` code block, whose `output.txt` shows a compiler error, or
+// that have a `does-not-compile` file explaining why. Returns their paths
+// relative to the listings directory.
+fn listings_expected_to_fail(
+    src_dir: &Path,
+    listings_dir: &Path,
+) -> Result<BTreeSet<PathBuf>, Box<dyn Error>> {
+    let mut expected_failures = BTreeSet::new();
+
+    for chapter in fs::read_dir(src_dir)? {
+        let chapter_path = chapter?.path();
+        if chapter_path.extension().map_or(true, |ext| ext != "md") {
+            continue;
+        }
+        let contents = fs::read_to_string(chapter_path)?;
+        let mut fence_info: Option<&str> = None;
+        for line in contents.lines() {
+            if let Some(info) = line.trim_start().strip_prefix("```") {
+                fence_info = match fence_info {
+                    Some(_) => None,
+                    None => Some(info),
+                };
+                continue;
+            }
+            let does_not_compile = fence_info.map_or(false, |info| {
+                info.split(',')
+                    .any(|attr| attr.trim() == "does_not_compile")
+            });
+            if does_not_compile {
+                for caps in LISTING_INCLUDE.captures_iter(line) {
+                    expected_failures
+                        .insert(Path::new(&caps[1]).join(&caps[2]));
+                }
+            }
+        }
+    }
+
+    for entry in walkdir::WalkDir::new(listings_dir)
+        .min_depth(3)
+        .max_depth(3)
+    {
+        let entry = entry?;
+        let fails = if entry.file_name() == "output.txt" {
+            fs::read_to_string(entry.path())?
+                .contains("error: could not compile")
+        } else {
+            entry.file_name() == "does-not-compile"
+        };
+        if fails {
+            let listing = entry
+                .path()
+                .parent()
+                .expect("File should've been in a listing")
+                .strip_prefix(listings_dir)?;
+            expected_failures.insert(listing.to_owned());
+        }
+    }
+
+    Ok(expected_failures)
+}
+
+// Writes a `Cargo.toml` in `workspace_dir` with every listing crate below it
+// as a member, so one `cargo build --workspace` builds them all with a shared
+// `target` directory and `Cargo.lock`.
+//
+// Listings that are workspaces themselves, like the `add` crates in chapter
+// 14, can't be nested in another workspace, and listings that don't compile
+// on purpose would fail the build; both are excluded and still build on their
+// own. Cargo requires unique package names within a workspace, so members are
+// renamed after their listing, keeping their library name so the code in the
+// listing still compiles.
+fn write_workspace(
+    workspace_dir: &Path,
+    out_dir: &Path,
+    expected_failures: &BTreeSet<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut manifests = Vec::new();
+    let walker = walkdir::WalkDir::new(workspace_dir)
+        .min_depth(1)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_name() == "Cargo.toml" {
+            let manifest =
+                fs::read_to_string(entry.path())?.parse::<Document>()?;
+            let crate_dir = entry
+                .path()
+                .parent()
+                .expect("Manifest should've been in a directory")
+                .to_owned();
+            manifests.push((crate_dir, manifest.contains_key("workspace")));
+        }
+    }
+
+    let nested_workspaces: Vec<&PathBuf> = manifests
+        .iter()
+        .filter(|(_, is_workspace)| *is_workspace)
+        .map(|(crate_dir, _)| crate_dir)
+        .collect();
+    let mut excluded = Vec::new();
+    let mut members = Vec::new();
+    for (crate_dir, is_workspace) in &manifests {
+        let listing = listing_path(out_dir, crate_dir)?;
+        if *is_workspace || expected_failures.contains(&listing) {
+            excluded.push(crate_dir.clone());
+        } else if !nested_workspaces
+            .iter()
+            .any(|workspace| crate_dir.starts_with(workspace))
+        {
+            members.push(crate_dir.clone());
+        }
+    }
+
+    let renames = members
+        .iter()
+        .map(|crate_dir| {
+            let name = member_package_name(crate_dir.strip_prefix(out_dir)?);
+            Ok((crate_dir.clone(), name))
+        })
+        .collect::<Result<BTreeMap<_, _>, Box<dyn Error>>>()?;
+    for crate_dir in &members {
+        rename_member(crate_dir, &renames)?;
+    }
+
+    let relative_paths = |dirs: &[PathBuf]| -> Result<Array, Box<dyn Error>> {
+        let mut array = Array::new();
+        for dir in dirs {
+            array.push(manifest_path(dir.strip_prefix(workspace_dir)?));
+        }
+        for item in array.iter_mut() {
+            item.decor_mut().set_prefix("\n    ");
+        }
+        array.set_trailing("\n");
+        array.set_trailing_comma(true);
+        Ok(array)
+    };
+
+    let mut workspace = Table::new();
+    workspace.insert("resolver", value("2"));
+    workspace.insert("members", value(relative_paths(&members)?));
+    workspace.insert("exclude", value(relative_paths(&excluded)?));
+    let mut manifest = Document::new();
+    manifest.insert("workspace", Item::Table(workspace));
+
+    let header = "\
+# Generated by `release_listings`: `cargo build --workspace` builds every
+# listing below that's expected to compile. The excluded listings don't compile
+# on purpose or are workspaces themselves; build those in their own directory.
+";
+    fs::write(
+        workspace_dir.join("Cargo.toml"),
+        format!("{}{}", header, manifest),
+    )?;
+
+    Ok(())
+}
+
+// The listing a crate belongs to, relative to the listings directory, such as
+// `ch14-more-about-cargo/listing-14-07` for its `add/adder` crate.
+fn listing_path(
+    out_dir: &Path,
+    crate_dir: &Path,
+) -> Result<PathBuf, Box<dyn Error>> {
+    Ok(crate_dir
+        .strip_prefix(out_dir)?
+        .components()
+        .take(2)
+        .collect())
+}
+
+// `ch19-advanced-features/listing-19-31/hello_macro` becomes
+// `ch19-listing-19-31-hello_macro`
+fn member_package_name(relative_to_listings: &Path) -> String {
+    let mut components = relative_to_listings
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy());
+    let chapter = components.next().unwrap_or_default();
+    let chapter_number = chapter.split('-').next().unwrap_or_default();
+    std::iter::once(chapter_number.into())
+        .chain(components)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// Paths in manifests always use `/`, whatever the platform
+fn manifest_path(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn rename_member(
+    crate_dir: &Path,
+    renames: &BTreeMap<PathBuf, String>,
+) -> Result<(), Box<dyn Error>> {
+    let manifest_path = crate_dir.join("Cargo.toml");
+    let mut manifest =
+        fs::read_to_string(&manifest_path)?.parse::<Document>()?;
+
+    let old_name = manifest["package"]["name"]
+        .as_str()
+        .ok_or_else(|| {
+            format!("{} has no package name", manifest_path.display())
+        })?
+        .to_string();
+    manifest["package"]["name"] = value(renames[crate_dir].as_str());
+
+    let has_lib = manifest.contains_key("lib")
+        || crate_dir.join("src").join("lib.rs").is_file();
+    if has_lib
+        && manifest
+            .get("lib")
+            .and_then(|lib| lib.get("name"))
+            .is_none()
+    {
+        let lib_name = value(old_name.replace('-', "_"));
+        match manifest.get_mut("lib").and_then(Item::as_table_like_mut) {
+            Some(lib) => {
+                lib.insert("name", lib_name);
+            }
+            None => {
+                let mut lib = Table::new();
+                lib.insert("name", lib_name);
+                manifest.insert("lib", Item::Table(lib));
+            }
+        }
+    }
+
+    // Dependencies on other renamed members keep their name in the code
+    for table in ["dependencies", "dev-dependencies", "build-dependencies"] {
+        let dependencies = match manifest
+            .get_mut(table)
+            .and_then(|item| item.as_table_like_mut())
+        {
+            Some(dependencies) => dependencies,
+            None => continue,
+        };
+        for (_, dependency) in dependencies.iter_mut() {
+            let dependency = match dependency.as_table_like_mut() {
+                Some(dependency) => dependency,
+                None => continue,
+            };
+            let dependency_dir =
+                match dependency.get("path").and_then(|path| path.as_str()) {
+                    Some(path) => normalize(&crate_dir.join(path)),
+                    None => continue,
+                };
+            if let Some(new_name) = renames.get(&dependency_dir) {
+                dependency.insert("package", value(new_name.as_str()));
+                dependency.fmt();
+            }
+        }
+    }
+
+    fs::write(manifest_path, manifest.to_string())?;
+    Ok(())
+}
+
+// Resolves `..` in paths such as `pancakes/../hello_macro` without touching
+// the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            std::path::Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+// Runs `cargo check` on every cleaned crate that should compile. When one
+// fails, a copy of the listing from before the cleaning is checked too, so
+// only failures the cleaning caused count as regressions. Returns the crates
+// that regressed, relative to the listings directory.
+fn verify_listings(
+    listings_dir: &Path,
+    out_dir: &Path,
+    verify_dir: &Path,
+    expected_failures: &BTreeSet<PathBuf>,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    // Listings in different directories share package names, and Cargo mixes
+    // their build artifacts up in a shared target directory, so every crate
+    // gets its own. Cargo runs in the crate, so the path has to be absolute.
+    let target_dir = env::current_dir()?.join(verify_dir).join("target");
+    let mut regressions = Vec::new();
+
+    for crate_dir in crate_dirs(out_dir)? {
+        let listing = listing_path(out_dir, &crate_dir)?;
+        if expected_failures.contains(&listing) {
+            continue;
+        }
+        let relative_to_listings = crate_dir.strip_prefix(out_dir)?;
+        println!("Checking {}", relative_to_listings.display());
+
+        // Cargo writes lock files, so it checks copies of the listings,
+        // which keeps them out of the release too
+        let cleaned = check_copy(
+            out_dir,
+            &listing,
+            relative_to_listings,
+            &verify_dir.join("cleaned"),
+            &target_dir.join("cleaned"),
+        )?;
+        if cleaned.status.success() {
+            continue;
+        }
+        let original = check_copy(
+            listings_dir,
+            &listing,
+            relative_to_listings,
+            &verify_dir.join("original"),
+            &target_dir.join("original"),
+        )?;
+
+        if original.status.success() {
+            eprintln!("Cleaning broke {}:", relative_to_listings.display());
+            eprint!("{}", String::from_utf8_lossy(&cleaned.stderr));
+            regressions.push(relative_to_listings.to_owned());
+        } else {
+            println!(
+                "{} doesn't compile before cleaning either; add a \
+                 `does-not-compile` file explaining why",
+                relative_to_listings.display()
+            );
+        }
+    }
+
+    Ok(regressions)
+}
+
+// Every directory with a `Cargo.toml` is a crate to check, except for members
+// of a workspace: checking the workspace root checks them too.
+fn crate_dirs(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let walker = walkdir::WalkDir::new(dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == "Cargo.toml" {
+            let crate_dir = entry
+                .path()
+                .parent()
+                .expect("Manifest should've been in a directory");
+            if !dirs.iter().any(|other| crate_dir.starts_with(other)) {
+                dirs.push(crate_dir.to_owned());
+            }
+        }
+    }
+    Ok(dirs)
+}
+
+// Copies `listing` from `listings_dir` to `copies_dir` and checks the crate at
+// `relative_to_listings` in the copy
+fn check_copy(
+    listings_dir: &Path,
+    listing: &Path,
+    relative_to_listings: &Path,
+    copies_dir: &Path,
+    target_dir: &Path,
+) -> Result<std::process::Output, Box<dyn Error>> {
+    let copied_listing = copies_dir.join(listing);
+    if copied_listing.is_dir() {
+        fs::remove_dir_all(&copied_listing)?;
+    }
+    copy_dir(&listings_dir.join(listing), &copied_listing)?;
+    cargo_check(
+        &copied_listing.join(relative_to_listings.strip_prefix(listing)?),
+        &target_dir.join(relative_to_listings),
+    )
+}
+
+fn cargo_check(
+    crate_dir: &Path,
+    target_dir: &Path,
+) -> Result<std::process::Output, Box<dyn Error>> {
+    let output = Command::new("cargo")
+        .args(["check", "--workspace", "--all-targets", "--offline"])
+        .arg("--quiet")
+        .env("CARGO_TARGET_DIR", target_dir)
+        .current_dir(crate_dir)
+        .output()?;
+    Ok(output)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    let walker = walkdir::WalkDir::new(from)
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        let output_path = to.join(entry.path().strip_prefix(from)?);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(output_path)?;
+        } else {
+            fs::copy(entry.path(), output_path)?;
+        }
+    }
+    Ok(())
+}
+
+// Runs `cargo vendor` for the workspaces and the nested workspaces they
+// exclude, and points Cargo at the `vendor` directory in `vendor_root` through
+// a `.cargo/config.toml` next to it. Listings that don't compile on purpose
+// are left out, since some of them can't even be loaded by Cargo.
+fn vendor_dependencies(
+    vendor_root: &Path,
+    workspace_dirs: &[&PathBuf],
+    out_dir: &Path,
+    expected_failures: &BTreeSet<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let mut manifests = Vec::new();
+    for workspace_dir in workspace_dirs {
+        let manifest_path = workspace_dir.join("Cargo.toml");
+        let workspace =
+            fs::read_to_string(&manifest_path)?.parse::<Document>()?;
+        manifests.push(manifest_path);
+        if let Some(excluded) = workspace["workspace"]["exclude"].as_array() {
+            for dir in excluded.iter().filter_map(|dir| dir.as_str()) {
+                let crate_dir = workspace_dir.join(dir);
+                let listing = listing_path(out_dir, &crate_dir)?;
+                if !expected_failures.contains(&listing) {
+                    manifests.push(crate_dir.join("Cargo.toml"));
+                }
+            }
+        }
+    }
+
+    let mut command = Command::new("cargo");
+    command.arg("vendor").current_dir(vendor_root);
+    for (i, manifest_path) in manifests.iter().enumerate() {
+        let manifest_path = manifest_path.strip_prefix(vendor_root)?;
+        command.arg(if i == 0 { "--manifest-path" } else { "--sync" });
+        command.arg(manifest_path);
+    }
+
+    let output = command.output()?;
+    if !output.status.success() {
+        return Err(format!(
+            "`cargo vendor` failed in {}:\n{}",
+            vendor_root.display(),
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+
+    // `cargo vendor` prints the configuration needed to use the vendored
+    // sources
+    let cargo_config_dir = vendor_root.join(".cargo");
+    fs::create_dir_all(&cargo_config_dir)?;
+    fs::write(cargo_config_dir.join("config.toml"), output.stdout)?;
 
     Ok(())
 }
 
-// Cleaned listings will not contain:
+// Archives everything in `dir` under a top-level directory named `root`.
 //
-// - `target` directories
-// - `output.txt` files used to display output in the book
-// - `rustfmt-ignore` files used to signal to update-rustc.sh the listing shouldn't be formatted
-// - anchor comments or snip comments
-// - empty `main` functions in `lib.rs` files used to trick rustdoc
+// Two runs on the same listings produce byte-identical archives: entries are
+// sorted, and timestamps, permissions and owners don't come from the machine
+// building the archive.
+fn write_archive(
+    format: Format,
+    archive_path: &Path,
+    root: &str,
+    dir: &Path,
+    mtime: u64,
+) -> Result<(), Box<dyn Error>> {
+    let file = File::create(archive_path)?;
+    match format {
+        Format::TarGz => {
+            // The gzip header would otherwise contain the current time
+            let encoder = flate2::GzBuilder::new()
+                .mtime(mtime as u32)
+                .operating_system(255)
+                .write(file, flate2::Compression::default());
+            let mut archive = tar::Builder::new(encoder);
+            for entry in archive_entries(root, dir)? {
+                let mut header = tar::Header::new_gnu();
+                header.set_uid(0);
+                header.set_gid(0);
+                header.set_mtime(mtime);
+                if entry.is_dir {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    header.set_mode(0o755);
+                    header.set_size(0);
+                    archive.append_data(
+                        &mut header,
+                        entry.name,
+                        io::empty(),
+                    )?;
+                } else {
+                    header.set_entry_type(tar::EntryType::Regular);
+                    header.set_mode(0o644);
+                    header.set_size(fs::metadata(&entry.path)?.len());
+                    archive.append_data(
+                        &mut header,
+                        entry.name,
+                        File::open(entry.path)?,
+                    )?;
+                }
+            }
+            archive.into_inner()?.finish()?;
+        }
+        Format::Zip => {
+            let mut archive = zip::ZipWriter::new(file);
+            let options = zip::write::FileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated)
+                .last_modified_time(zip_date_time(mtime)?);
+            for entry in archive_entries(root, dir)? {
+                if entry.is_dir {
+                    archive.add_directory(
+                        entry.name,
+                        options.unix_permissions(0o755),
+                    )?;
+                } else {
+                    archive.start_file(
+                        entry.name,
+                        options.unix_permissions(0o644),
+                    )?;
+                    io::copy(&mut File::open(entry.path)?, &mut archive)?;
+                }
+            }
+            archive.finish()?;
+        }
+    }
+    Ok(())
+}
+
+struct ArchiveEntry {
+    name: String,
+    path: PathBuf,
+    is_dir: bool,
+}
+
+// Returns everything in `dir`, sorted by name.
+fn archive_entries(
+    root: &str,
+    dir: &Path,
+) -> Result<Vec<ArchiveEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    for entry in walkdir::WalkDir::new(dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+    {
+        let entry = entry?;
+        let relative_path = entry.path().strip_prefix(dir)?;
+        let name = Path::new(root)
+            .join(relative_path)
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        entries.push(ArchiveEntry {
+            name,
+            path: entry.path().to_owned(),
+            is_dir: entry.file_type().is_dir(),
+        });
+    }
+    Ok(entries)
+}
+
+// The timestamp of every archive entry, following
+// https://reproducible-builds.org/specs/source-date-epoch/
+fn source_date_epoch() -> Result<u64, Box<dyn Error>> {
+    match env::var("SOURCE_DATE_EPOCH") {
+        Ok(epoch) => epoch.parse().map_err(|_| {
+            format!("Invalid SOURCE_DATE_EPOCH `{}`", epoch).into()
+        }),
+        Err(_) => Ok(0),
+    }
+}
+
+// Zip files store local dates that can't be before 1980, so earlier times are
+// clamped to the start of 1980.
+fn zip_date_time(epoch: u64) -> Result<zip::DateTime, Box<dyn Error>> {
+    let days = (epoch / 86_400) as i64;
+    let seconds = epoch % 86_400;
+
+    // Converts days since 1970-01-01 into a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524
+        - day_of_era / 146_096)
+        / 365;
+    let day_of_year =
+        day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    if year < 1980 {
+        return Ok(zip::DateTime::default());
+    }
+    zip::DateTime::from_date_and_time(
+        year as u16,
+        month as u8,
+        day as u8,
+        (seconds / 3600) as u8,
+        (seconds % 3600 / 60) as u8,
+        (seconds % 60) as u8,
+    )
+    .map_err(|_| {
+        format!("SOURCE_DATE_EPOCH {} is too late for zip", epoch).into()
+    })
+}
+
+// Writes a `SHA256SUMS` file next to the archives that `sha256sum -c` can
+// check.
+fn write_checksums(
+    output_dir: &Path,
+    archives: &[PathBuf],
+) -> Result<PathBuf, Box<dyn Error>> {
+    let mut checksums = String::new();
+    for archive in archives {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(archive)?, &mut hasher)?;
+        let file_name = archive
+            .file_name()
+            .expect("Archive should've had a name")
+            .to_string_lossy();
+        checksums.push_str(&format!(
+            "{:x}  {}\n",
+            hasher.finalize(),
+            file_name
+        ));
+    }
+
+    let checksums_path = output_dir.join("SHA256SUMS");
+    fs::write(&checksums_path, checksums)?;
+    Ok(checksums_path)
+}
+
+// The cleaning rules come from a TOML file, by default
+// `tools/release_listings.toml`, so translations with their own snip comments
+// can reuse this tool.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CleaningConfig {
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    rules: Vec<FileRuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct FileRuleConfig {
+    files: String,
+    #[serde(default)]
+    remove_lines: Vec<String>,
+}
+
+struct CleaningRules {
+    exclude: GlobSet,
+    rules: Vec<FileRule>,
+}
+
+struct FileRule {
+    files: GlobMatcher,
+    remove_lines: RegexSet,
+}
+
+impl CleaningRules {
+    fn from_config(contents: &str) -> Result<CleaningRules, Box<dyn Error>> {
+        let config: CleaningConfig = toml::from_str(contents)?;
+
+        let mut exclude = GlobSetBuilder::new();
+        for pattern in &config.exclude {
+            exclude.add(Glob::new(pattern)?);
+        }
+
+        let rules = config
+            .rules
+            .iter()
+            .map(|rule| {
+                Ok(FileRule {
+                    files: Glob::new(&rule.files)?.compile_matcher(),
+                    remove_lines: RegexSet::new(&rule.remove_lines)?,
+                })
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(CleaningRules {
+            exclude: exclude.build()?,
+            rules,
+        })
+    }
+
+    // `relative_path` is relative to the listings directory
+    fn is_excluded(&self, relative_path: &Path) -> bool {
+        self.exclude.is_match(relative_path)
+    }
+
+    fn rules_for(&self, file_name: &OsStr) -> Vec<&FileRule> {
+        self.rules
+            .iter()
+            .filter(|rule| rule.files.is_match(file_name))
+            .collect()
+    }
+}
+
+// Copies the files of a listing, leaving out the excluded ones and the lines
+// that the rules for each file remove.
 fn copy_cleaned_listing_files(
+    rules: &CleaningRules,
+    listings_dir: &Path,
     from: PathBuf,
     to: PathBuf,
 ) -> Result<(), Box<dyn Error>> {
@@ -84,30 +1342,29 @@ fn copy_cleaned_listing_files(
         let item = item?;
         let item_path = item.path();
 
+        if rules.is_excluded(item_path.strip_prefix(listings_dir)?) {
+            continue;
+        }
+
         let item_name =
             item_path.file_name().expect("Item should've had a name");
         let output_item = to.join(item_name);
 
         if item_path.is_dir() {
-            // Don't copy `target` directories
-            if item_name != "target" {
-                fs::create_dir(&output_item)?;
-                copy_cleaned_listing_files(item_path, output_item)?;
-            }
+            fs::create_dir(&output_item)?;
+            copy_cleaned_listing_files(
+                rules,
+                listings_dir,
+                item_path,
+                output_item,
+            )?;
         } else {
-            // Don't copy output files or files that tell update-rustc.sh not to format
-            if item_name != "output.txt" && item_name != "rustfmt-ignore" {
-                let item_extension = item_path.extension();
-                if item_extension.is_some() && item_extension.unwrap() == "rs" {
-                    copy_cleaned_rust_file(
-                        item_name,
-                        &item_path,
-                        &output_item,
-                    )?;
-                } else {
-                    // Copy any non-Rust files without modification
-                    fs::copy(item_path, output_item)?;
-                }
+            let file_rules = rules.rules_for(item_name);
+            if file_rules.is_empty() {
+                // Copy files without rules without modification
+                fs::copy(item_path, output_item)?;
+            } else {
+                copy_cleaned_file(&file_rules, &item_path, &output_item)?;
             }
         }
     }
@@ -115,41 +1372,21 @@ fn copy_cleaned_listing_files(
     Ok(())
 }
 
-lazy_static! {
-    static ref ANCHOR_OR_SNIP_COMMENTS: Regex = Regex::new(
-        r"(?x)
-    //\s*ANCHOR:\s*[\w_-]+      # Remove all anchor comments
-    |
-    //\s*ANCHOR_END:\s*[\w_-]+  # Remove all anchor ending comments
-    |
-    //\s*--省略--               # Remove all snip comments
-    "
-    )
-    .unwrap();
-}
-
-lazy_static! {
-    static ref EMPTY_MAIN: Regex = Regex::new(r"fn main\(\) \{}").unwrap();
-}
-
-// Cleaned Rust files will not contain:
-//
-// - anchor comments or snip comments
-// - empty `main` functions in `lib.rs` files used to trick rustdoc
-fn copy_cleaned_rust_file(
-    item_name: &std::ffi::OsStr,
-    from: &PathBuf,
-    to: &PathBuf,
+fn copy_cleaned_file(
+    file_rules: &[&FileRule],
+    from: &Path,
+    to: &Path,
 ) -> Result<(), Box<dyn Error>> {
     let from_buf = BufReader::new(File::open(from)?);
     let mut to_buf = BufWriter::new(File::create(to)?);
 
     for line in from_buf.lines() {
         let line = line?;
-        if !ANCHOR_OR_SNIP_COMMENTS.is_match(&line) {
-            if item_name != "lib.rs" || !EMPTY_MAIN.is_match(&line) {
-                writeln!(&mut to_buf, "{}", line)?;
-            }
+        if !file_rules
+            .iter()
+            .any(|rule| rule.remove_lines.is_match(&line))
+        {
+            writeln!(&mut to_buf, "{}", line)?;
         }
     }
 
@@ -157,3 +1394,235 @@ fn copy_cleaned_rust_file(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    #[test]
+    fn member_package_name_is_unique_across_chapters() {
+        assert_eq!(
+            super::member_package_name(Path::new(
+                "ch04-understanding-ownership/no-listing-01-can-mutate-string"
+            )),
+            "ch04-no-listing-01-can-mutate-string"
+        );
+        assert_eq!(
+            super::member_package_name(Path::new(
+                "ch19-advanced-features/listing-19-31/hello_macro/hello_macro_derive"
+            )),
+            "ch19-listing-19-31-hello_macro-hello_macro_derive"
+        );
+    }
+
+    #[test]
+    fn normalize_resolves_parent_directories() {
+        assert_eq!(
+            super::normalize(Path::new(
+                "listing-19-33/pancakes/../hello_macro"
+            )),
+            Path::new("listing-19-33/hello_macro")
+        );
+    }
+
+    #[test]
+    fn zip_date_time_converts_the_source_date_epoch() {
+        // 2021-10-21T09:05:30Z
+        let date_time = super::zip_date_time(1_634_807_130).unwrap();
+
+        assert_eq!(date_time.year(), 2021);
+        assert_eq!(date_time.month(), 10);
+        assert_eq!(date_time.day(), 21);
+        assert_eq!(date_time.hour(), 9);
+        assert_eq!(date_time.minute(), 5);
+        assert_eq!(date_time.second(), 30);
+    }
+
+    #[test]
+    fn zip_date_time_clamps_dates_before_1980() {
+        let date_time = super::zip_date_time(0).unwrap();
+
+        assert_eq!(date_time.year(), 1980);
+        assert_eq!(date_time.month(), 1);
+        assert_eq!(date_time.day(), 1);
+    }
+
+    #[test]
+    fn output_listings_dir_refuses_the_listings_and_their_parents() {
+        let listings = Path::new("listings");
+
+        assert!(super::output_listings_dir(listings, Path::new(".")).is_err());
+        assert!(super::output_listings_dir(
+            listings,
+            Path::new("listings/ch01-getting-started")
+        )
+        .is_err());
+        assert_eq!(
+            super::output_listings_dir(listings, Path::new("tools")).unwrap(),
+            Path::new("tools/listings")
+        );
+    }
+
+    #[test]
+    fn is_selected_chapter_selects_every_chapter_without_a_filter() {
+        assert!(super::is_selected_chapter("ch12-an-io-project", &[]));
+    }
+
+    #[test]
+    fn is_selected_chapter_matches_the_chapter_number_prefix() {
+        let filter = ["ch12", "ch20"];
+
+        assert!(super::is_selected_chapter("ch12-an-io-project", &filter));
+        assert!(super::is_selected_chapter("ch20-web-server", &filter));
+        assert!(!super::is_selected_chapter(
+            "ch02-guessing-game-tutorial",
+            &filter
+        ));
+    }
+
+    #[test]
+    fn is_selected_chapter_does_not_match_a_longer_chapter_number() {
+        assert!(!super::is_selected_chapter("ch120-imaginary", &["ch12"]));
+    }
+
+    #[test]
+    fn default_cleaning_rules_exclude_output_and_marker_files() {
+        let config =
+            std::fs::read_to_string("tools/release_listings.toml").unwrap();
+        let rules = super::CleaningRules::from_config(&config).unwrap();
+
+        assert!(rules.is_excluded(Path::new(
+            "ch02-guessing-game-tutorial/listing-02-01/output.txt"
+        )));
+        assert!(rules.is_excluded(Path::new(
+            "ch14-more-about-cargo/listing-14-07/add/rustfmt-ignore"
+        )));
+        assert!(rules.is_excluded(Path::new(
+            "ch02-guessing-game-tutorial/listing-02-01/target"
+        )));
+        assert!(!rules.is_excluded(Path::new(
+            "ch02-guessing-game-tutorial/listing-02-01/src/main.rs"
+        )));
+    }
+
+    #[test]
+    fn default_cleaning_rules_remove_anchors_and_empty_main_in_lib_rs() {
+        let config =
+            std::fs::read_to_string("tools/release_listings.toml").unwrap();
+        let rules = super::CleaningRules::from_config(&config).unwrap();
+        let removes = |file_name: &str, line: &str| {
+            rules
+                .rules_for(file_name.as_ref())
+                .iter()
+                .any(|rule| rule.remove_lines.is_match(line))
+        };
+
+        assert!(removes("main.rs", "    // ANCHOR: here"));
+        assert!(removes("main.rs", "// ANCHOR_END: here"));
+        assert!(removes("main.rs", "    // --省略--"));
+        assert!(removes("lib.rs", "fn main() {}"));
+        assert!(!removes("main.rs", "fn main() {}"));
+        assert!(!removes("Cargo.toml", "# ANCHOR: here"));
+    }
+
+    #[test]
+    fn cleaning_rules_reject_unknown_keys() {
+        let config = r#"
+[[rules]]
+files = "*.rs"
+remove-line = ['// TODO']
+"#;
+
+        assert!(super::CleaningRules::from_config(config).is_err());
+    }
+
+    #[test]
+    fn crate_dirs_checks_workspace_members_through_the_workspace() {
+        let dirs = super::crate_dirs(Path::new(
+            "listings/ch14-more-about-cargo/listing-14-07",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            dirs,
+            vec![Path::new(
+                "listings/ch14-more-about-cargo/listing-14-07/add"
+            )]
+        );
+    }
+
+    #[test]
+    fn summary_titles_maps_section_files_to_titles() {
+        let summary = "\
+- [撰寫命令列程式](ch12-00-an-io-project.md)
+    - [接受命令列引數](ch12-01-accepting-command-line-arguments.md)
+";
+
+        let titles = super::summary_titles(summary);
+
+        assert_eq!(titles["ch12-00-an-io-project.md"], "撰寫命令列程式");
+        assert_eq!(
+            titles["ch12-01-accepting-command-line-arguments.md"],
+            "接受命令列引數"
+        );
+    }
+
+    #[test]
+    fn listing_captions_joins_captions_over_several_lines() {
+        let contents = "\
+```rust,noplayground
+{{#rustdoc_include ../listings/ch12-an-io-project/listing-12-17/src/lib.rs:here}}
+```
+
+<span class=\"caption\">範例 12-17：在 `contents` 中遍歷每一行
+</span>
+
+```rust
+{{#rustdoc_include ../listings/ch12-an-io-project/no-listing-02-using-search-in-run/src/lib.rs:here}}
+```
+
+接著我們...
+";
+
+        assert_eq!(
+            super::listing_captions(contents),
+            vec![
+                (
+                    Path::new("ch12-an-io-project/listing-12-17").to_owned(),
+                    Some("範例 12-17：在 `contents` 中遍歷每一行".to_owned())
+                ),
+                (
+                    Path::new(
+                        "ch12-an-io-project/no-listing-02-using-search-in-run"
+                    )
+                    .to_owned(),
+                    None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn listing_captions_finds_output_regenerated_by_hand() {
+        let contents = "\
+<!-- manual-regeneration
+cd listings/ch02-guessing-game-tutorial/listing-02-02/
+cargo build -->
+
+```console
+$ cargo build
+```
+
+<span class=\"caption\">範例 2-2：執行 `cargo build` 的輸出</span>
+";
+
+        assert_eq!(
+            super::listing_captions(contents),
+            vec![(
+                Path::new("ch02-guessing-game-tutorial/listing-02-02")
+                    .to_owned(),
+                Some("範例 2-2：執行 `cargo build` 的輸出".to_owned())
+            )]
+        );
+    }
+}
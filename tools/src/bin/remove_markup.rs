@@ -28,10 +28,27 @@ fn remove_markup(input: String) -> String {
         Regex::new(r#"\A<span class="caption">(.*)\z"#).unwrap();
     let caption_end_regex = Regex::new(r#"(.*)</span>\z"#).unwrap();
     let regexen = vec![filename_regex, caption_start_regex, caption_end_regex];
+    // The lines `listing_figures` wraps listings in.
+    let figure_regex = Regex::new(
+        r#"\A(<figure class="listing">|</?figcaption>|</figure>)\z"#,
+    )
+    .unwrap();
+    let mut after_figure_markup = false;
 
     let lines: Vec<_> = input
         .lines()
         .flat_map(|line| {
+            // Remove the figure markup, and the blank line after it so the
+            // paragraphs stay apart by one.
+            if figure_regex.is_match(line) {
+                after_figure_markup = true;
+                return None;
+            }
+            if after_figure_markup && line.is_empty() {
+                after_figure_markup = false;
+                return None;
+            }
+            after_figure_markup = false;
             // Remove our syntax highlighting and rustdoc markers.
             if line.starts_with("```") {
                 Some(String::from("```"))
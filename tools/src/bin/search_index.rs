@@ -0,0 +1,377 @@
+use docopt::Docopt;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let terminology = Path::new(&args.flag_src).join(TERMINOLOGY);
+    let glossary = Glossary::parse(&fs::read_to_string(terminology)?);
+
+    let book = Path::new(args.arg_book_dir.as_deref().unwrap_or("book"));
+    let path = book.join("searchindex.json");
+    let json = fs::read_to_string(&path)?;
+    // The trie of the index is as deep as the longest token
+    let mut deserializer = serde_json::Deserializer::from_str(&json);
+    deserializer.disable_recursion_limit();
+    let mut search = Value::deserialize(&mut deserializer)?;
+    rebuild(&mut search["index"], &glossary)?;
+
+    let json = serde_json::to_string(&search)?;
+    fs::write(&path, &json)?;
+    // The index for pages opened from the file system
+    let js = book.join("searchindex.js");
+    if js.exists() {
+        fs::write(js, format!("Object.assign(window.search, {});", json))?;
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "
+search_index
+Rebuilds the `searchindex.json` of `mdbook build` so Chinese text is split
+into the terms of the terminology appendix, and the characters between them,
+instead of only at whitespace. The index also gets the glossary for
+`search-zh.js`, which splits queries the same way and adds the Chinese terms
+of the English ones they have.
+
+Usage:
+  search_index [options] [<book-dir>]
+  search_index (-h | --help)
+
+Options:
+  -h --help    Show this screen.
+  --src=<dir>  The source of the book [default: src].
+
+The book is in `book` when no <book-dir> is given.
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_book_dir: Option<String>,
+    flag_src: String,
+}
+
+const TERMINOLOGY: &str = "appendix-08-terminology.md";
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
+
+// The dictionary of the segmentation and the Chinese terms of each English
+// term, from the terminology table
+struct Glossary {
+    words: HashSet<String>,
+    longest: usize,
+    english: BTreeMap<String, Vec<String>>,
+}
+
+impl Glossary {
+    // Rows are `English | 中文、中文 | Note`
+    fn parse(markdown: &str) -> Glossary {
+        let mut glossary = Glossary {
+            words: HashSet::new(),
+            longest: 0,
+            english: BTreeMap::new(),
+        };
+        let rows = markdown
+            .lines()
+            .skip_while(|line| !line.starts_with("---"))
+            .skip(1)
+            .take_while(|line| line.contains('|'));
+        for row in rows {
+            let columns = row.split('|').map(str::trim).collect::<Vec<_>>();
+            let (english, translations) = match columns.as_slice() {
+                [english, translations, ..] => (english, translations),
+                _ => continue,
+            };
+            for term in translations.split('、').map(str::trim) {
+                if term.is_empty() {
+                    continue;
+                }
+                glossary
+                    .english
+                    .entry(english.to_lowercase())
+                    .or_default()
+                    .push(term.to_string());
+                let length = term.chars().count();
+                if length > 1 && term.chars().all(is_cjk) {
+                    glossary.words.insert(term.to_string());
+                    glossary.longest = glossary.longest.max(length);
+                }
+            }
+        }
+        glossary
+    }
+
+    // The number of characters of the longest word `chars` starts with, or
+    // one when it starts with no word
+    fn word_at(&self, chars: &[char]) -> usize {
+        (2..=self.longest.min(chars.len()))
+            .rev()
+            .find(|&length| {
+                let word = chars[..length].iter().collect::<String>();
+                self.words.contains(&word)
+            })
+            .unwrap_or(1)
+    }
+
+    fn to_json(&self) -> Value {
+        let mut words = self.words.iter().collect::<Vec<_>>();
+        words.sort();
+        json!({ "words": words, "english": self.english })
+    }
+}
+
+// The tokens of the pieces between whitespace and hyphens with Chinese in
+// them, which are all mdbook gets wrong. Chinese is segmented by the longest
+// word first, and the rest of the piece splits at anything that isn't a
+// letter or a digit. Those words aren't stemmed, but `expand` in the search
+// options still finds them from their stem.
+fn cjk_tokens(text: &str, glossary: &Glossary) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let pieces = text
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|piece| piece.chars().any(is_cjk));
+    for piece in pieces {
+        let chars = piece.to_lowercase().chars().collect::<Vec<_>>();
+        let mut latin = String::new();
+        let mut index = 0;
+        while index < chars.len() {
+            let c = chars[index];
+            if c.is_alphanumeric() && !is_cjk(c) || c == '_' {
+                latin.push(c);
+                index += 1;
+                continue;
+            }
+            if !latin.is_empty() {
+                tokens.push(std::mem::take(&mut latin));
+            }
+            if is_cjk(c) {
+                let length = glossary.word_at(&chars[index..]);
+                tokens.push(chars[index..index + length].iter().collect());
+                index += length;
+            } else {
+                index += 1;
+            }
+        }
+        if !latin.is_empty() {
+            tokens.push(latin);
+        }
+    }
+    tokens
+}
+
+// Replaces the tokens with Chinese in them in the elasticlunr index of
+// every field, keeping the lengths of the fields in `docInfo` right
+fn rebuild(
+    index: &mut Value,
+    glossary: &Glossary,
+) -> Result<(), Box<dyn Error>> {
+    let fields = index["fields"]
+        .as_array()
+        .ok_or("the search index has no fields")?
+        .iter()
+        .filter_map(|field| field.as_str().map(String::from))
+        .collect::<Vec<_>>();
+    let docs = index["documentStore"]["docs"]
+        .as_object()
+        .ok_or("the search index has no documents")?
+        .clone();
+
+    for field in &fields {
+        let mut tokens = BTreeMap::new();
+        collect_tokens(
+            &index["index"][field]["root"],
+            &mut String::new(),
+            &mut tokens,
+        );
+
+        // Term frequencies are the square root of the count
+        let mut lengths: HashMap<String, i64> = HashMap::new();
+        tokens.retain(|token: &String, docs: &mut BTreeMap<String, f64>| {
+            if !token.chars().any(is_cjk) {
+                return true;
+            }
+            for (doc, tf) in docs.iter() {
+                *lengths.entry(doc.clone()).or_default() -=
+                    (tf * tf).round() as i64;
+            }
+            false
+        });
+        for (doc, fields) in &docs {
+            let text = fields[field].as_str().unwrap_or("");
+            let mut counts: BTreeMap<String, f64> = BTreeMap::new();
+            for token in cjk_tokens(text, glossary) {
+                *counts.entry(token).or_default() += 1.0;
+                *lengths.entry(doc.clone()).or_default() += 1;
+            }
+            for (token, count) in counts {
+                let tf = tokens
+                    .entry(token)
+                    .or_default()
+                    .entry(doc.clone())
+                    .or_default();
+                *tf = (*tf * *tf + count).sqrt();
+            }
+        }
+
+        index["index"][field]["root"] = trie(&tokens);
+        for (doc, length) in lengths {
+            if let Some(info) =
+                index["documentStore"]["docInfo"][&doc].get_mut(field)
+            {
+                *info = json!(info.as_i64().unwrap_or(0) + length);
+            }
+        }
+    }
+    index["glossary"] = glossary.to_json();
+
+    Ok(())
+}
+
+// Nodes of the trie have a child for each next character, and the documents
+// of the token that ends at them
+fn collect_tokens(
+    node: &Value,
+    token: &mut String,
+    tokens: &mut BTreeMap<String, BTreeMap<String, f64>>,
+) {
+    let node = match node.as_object() {
+        Some(node) => node,
+        None => return,
+    };
+    for (key, child) in node {
+        match key.as_str() {
+            "docs" => {
+                let docs = child
+                    .as_object()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|(doc, frequency)| {
+                        Some((doc.clone(), frequency["tf"].as_f64()?))
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                if !docs.is_empty() {
+                    tokens.insert(token.clone(), docs);
+                }
+            }
+            "df" => {}
+            _ => {
+                token.push_str(key);
+                collect_tokens(child, token, tokens);
+                token.truncate(token.len() - key.len());
+            }
+        }
+    }
+}
+
+fn trie(tokens: &BTreeMap<String, BTreeMap<String, f64>>) -> Value {
+    let empty = || json!({ "docs": {}, "df": 0 });
+    let mut root = empty();
+    for (token, docs) in tokens {
+        let mut node = &mut root;
+        for c in token.chars() {
+            node = node
+                .as_object_mut()
+                .unwrap()
+                .entry(c.to_string())
+                .or_insert_with(empty);
+        }
+        node["docs"] = Value::Object(
+            docs.iter()
+                .map(|(doc, tf)| (doc.clone(), json!({ "tf": tf })))
+                .collect::<Map<_, _>>(),
+        );
+        node["df"] = json!(docs.len());
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glossary;
+    use serde_json::json;
+
+    const TERMINOLOGY: &str = "# 中英術語對照表
+
+English 英文           | Traditional Chinese 正體中文  | Note 備註
+------------           |-----------------------------  |----------
+borrowing              | 借用                          |
+borrow checker         | 借用檢查器                    |
+ownership              | 所有權                        |
+values                 | 值、數值                      |
+";
+
+    #[test]
+    fn chinese_is_split_into_the_longest_terms() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+
+        assert_eq!(
+            super::cjk_tokens("借用檢查器會檢查借用的數值。", &glossary),
+            ["借用檢查器", "會", "檢", "查", "借用", "的", "數值"]
+        );
+        assert_eq!(glossary.english["values"], ["值", "數值"]);
+    }
+
+    #[test]
+    fn only_pieces_with_chinese_are_tokenized() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+
+        assert_eq!(
+            super::cjk_tokens(
+                "Rust 的 String::from 所有權：Rust的x_1",
+                &glossary
+            ),
+            ["的", "所有權", "rust", "的", "x_1"]
+        );
+    }
+
+    #[test]
+    fn chinese_tokens_are_replaced_in_the_index() {
+        let glossary = Glossary::parse(TERMINOLOGY);
+        let mut index = json!({
+            "fields": ["body"],
+            "index": { "body": { "root": {
+                "docs": {}, "df": 0,
+                "r": { "docs": {}, "df": 1, "u": { "docs": {}, "df": 0,
+                    "s": { "docs": {}, "df": 0,
+                        "t": { "docs": { "0": { "tf": 1.0 } }, "df": 1 } } } },
+                "所": { "docs": {}, "df": 0,
+                    "有": { "docs": {}, "df": 0,
+                        "權": { "docs": {}, "df": 0,
+                            "規": { "docs": {}, "df": 0,
+                                "則": { "docs": { "0": { "tf": 1.0 } }, "df": 1 } } } } }
+            } } },
+            "documentStore": {
+                "docs": { "0": { "body": "Rust 所有權規則", "id": "0" } },
+                "docInfo": { "0": { "body": 2 } }
+            }
+        });
+
+        super::rebuild(&mut index, &glossary).unwrap();
+
+        let root = &index["index"]["body"]["root"];
+        assert_eq!(root["r"]["u"]["s"]["t"]["docs"]["0"]["tf"], 1.0);
+        assert_eq!(root["所"]["有"]["權"]["docs"]["0"]["tf"], 1.0);
+        assert!(root["所"]["有"]["權"]["規"].is_null());
+        assert_eq!(root["規"]["df"], 1);
+        assert_eq!(root["則"]["df"], 1);
+        assert_eq!(index["documentStore"]["docInfo"]["0"]["body"], 4);
+        assert_eq!(
+            index["glossary"]["english"]["ownership"],
+            json!(["所有權"])
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use docopt::Docopt;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use toml_edit::{value, Document};
+
+#[path = "../rustfmt_ignore.rs"]
+mod rustfmt_ignore;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Args = Docopt::new(USAGE)
+        .and_then(|d| d.deserialize())
+        .unwrap_or_else(|e| e.exit());
+
+    let listings_dir = Path::new(&args.flag_listings_dir);
+
+    let mut updated = Vec::new();
+    let mut not_fixed = Vec::new();
+    let mut fix_failures = Vec::new();
+
+    for manifest_path in listing_manifests(listings_dir)? {
+        let contents = fs::read_to_string(&manifest_path)?;
+        let mut manifest = contents.parse::<Document>()?;
+
+        if !has_edition(&manifest, &args.arg_old_edition) {
+            continue;
+        }
+
+        let crate_dir = manifest_path
+            .parent()
+            .expect("Manifest should've been in a directory");
+
+        // `cargo fix --edition` migrates the code from the edition that's in
+        // the manifest to the next one, so it has to run before the edition
+        // is changed. Listings rustfmt mustn't touch are migrated by hand,
+        // since `cargo fix` would rewrite them just the same.
+        if args.flag_fix {
+            match rustfmt_ignore::reason(listings_dir, crate_dir)? {
+                Some(reason) => not_fixed.push((crate_dir.to_owned(), reason)),
+                None => {
+                    if !cargo_fix_edition(crate_dir)? {
+                        fix_failures.push(crate_dir.to_owned());
+                    }
+                }
+            }
+        }
+
+        manifest["package"]["edition"] = value(args.arg_new_edition.as_str());
+        fs::write(&manifest_path, manifest.to_string())?;
+        updated.push(crate_dir.to_owned());
+    }
+
+    println!(
+        "Updated {} listing(s) from edition {} to {}.",
+        updated.len(),
+        args.arg_old_edition,
+        args.arg_new_edition
+    );
+
+    if !not_fixed.is_empty() {
+        println!("\nSkipped `cargo fix` because of a `rustfmt-ignore` file:");
+        for (crate_dir, reason) in &not_fixed {
+            println!("  {}: {}", crate_dir.display(), reason);
+        }
+    }
+
+    if !fix_failures.is_empty() {
+        println!("\n`cargo fix` failed, migrate these by hand:");
+        for crate_dir in &fix_failures {
+            println!("  {}", crate_dir.display());
+        }
+    }
+
+    let outputs = outputs_to_regenerate(listings_dir, &updated);
+    if !outputs.is_empty() {
+        println!("\nRegenerate these with `tools/update-rustc.sh`:");
+        for output in &outputs {
+            println!("  {}", output.display());
+        }
+    }
+
+    Ok(())
+}
+
+const USAGE: &str = "
+update_edition
+Updates the edition of every listing that is on <old-edition>.
+
+Usage:
+  update_edition [options] <old-edition> <new-edition>
+  update_edition (-h | --help)
+
+Options:
+  -h --help              Show this screen.
+  --fix                  Run `cargo fix --edition` in each listing first.
+  --listings-dir=<dir>   Where the listings are [default: listings].
+";
+
+#[derive(Debug, Deserialize)]
+struct Args {
+    arg_old_edition: String,
+    arg_new_edition: String,
+    flag_fix: bool,
+    flag_listings_dir: String,
+}
+
+fn listing_manifests(
+    listings_dir: &Path,
+) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut manifests = Vec::new();
+    let walker = walkdir::WalkDir::new(listings_dir)
+        .sort_by(|a, b| a.file_name().cmp(b.file_name()))
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != "target");
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() == "Cargo.toml" {
+            manifests.push(entry.into_path());
+        }
+    }
+    Ok(manifests)
+}
+
+// Workspace roots like the `add` crates in chapter 14 have no `[package]`
+// table; their members are updated through their own manifests.
+fn has_edition(manifest: &Document, edition: &str) -> bool {
+    manifest
+        .get("package")
+        .and_then(|package| package.get("edition"))
+        .and_then(|edition| edition.as_str())
+        .map_or(false, |current| current == edition)
+}
+
+fn cargo_fix_edition(crate_dir: &Path) -> Result<bool, Box<dyn Error>> {
+    println!("Running `cargo fix --edition` in {}", crate_dir.display());
+    let status = Command::new("cargo")
+        .args(["fix", "--edition", "--allow-dirty", "--allow-no-vcs"])
+        .current_dir(crate_dir)
+        .status()?;
+    Ok(status.success())
+}
+
+// The listing directory is the one directly inside a chapter directory, and
+// that's where its `output.txt` lives even if the listing is a workspace.
+fn listing_dir(listings_dir: &Path, crate_dir: &Path) -> Option<PathBuf> {
+    let relative = crate_dir.strip_prefix(listings_dir).ok()?;
+    let mut components = relative.components();
+    let chapter = components.next()?;
+    let listing = components.next()?;
+    Some(listings_dir.join(chapter).join(listing))
+}
+
+fn outputs_to_regenerate(
+    listings_dir: &Path,
+    updated: &[PathBuf],
+) -> BTreeSet<PathBuf> {
+    updated
+        .iter()
+        .filter_map(|crate_dir| listing_dir(listings_dir, crate_dir))
+        .map(|listing| listing.join("output.txt"))
+        .filter(|output| output.is_file())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn has_edition_matches_the_package_edition() {
+        let manifest = r#"[package]
+name = "ownership"
+version = "0.1.0"
+edition = "2018"
+
+[dependencies]
+"#
+        .parse()
+        .unwrap();
+
+        assert!(super::has_edition(&manifest, "2018"));
+        assert!(!super::has_edition(&manifest, "2021"));
+    }
+
+    #[test]
+    fn has_edition_is_false_for_workspace_roots() {
+        let manifest = r#"[workspace]
+
+members = [
+    "adder",
+    "add_one",
+]
+"#
+        .parse()
+        .unwrap();
+
+        assert!(!super::has_edition(&manifest, "2018"));
+    }
+
+    #[test]
+    fn listing_dir_finds_the_listing_of_a_workspace_member() {
+        let listing = super::listing_dir(
+            Path::new("listings"),
+            Path::new("listings/ch14-more-about-cargo/listing-14-07/add/adder"),
+        );
+
+        assert_eq!(
+            listing,
+            Some(PathBuf::from(
+                "listings/ch14-more-about-cargo/listing-14-07"
+            ))
+        );
+    }
+}
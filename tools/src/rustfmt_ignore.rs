@@ -0,0 +1,25 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+// There are a handful of listings we don't want to rustfmt and skipping
+// doesn't work; those have a `rustfmt-ignore` file in their directory, or in
+// the workspace they're part of, that explains why.
+pub fn reason(
+    listings_dir: &Path,
+    crate_dir: &Path,
+) -> Result<Option<String>, Box<dyn Error>> {
+    for dir in crate_dir.ancestors() {
+        let marker = dir.join("rustfmt-ignore");
+        if marker.is_file() {
+            let reason = fs::read_to_string(marker)?;
+            return Ok(Some(
+                reason.split_whitespace().collect::<Vec<_>>().join(" "),
+            ));
+        }
+        if dir == listings_dir {
+            break;
+        }
+    }
+    Ok(None)
+}
@@ -6,19 +6,11 @@ set -eu
 echo 'Building book into tmp/book-before before updating...'
 mdbook build -d tmp/book-before
 
-# Rustfmt all listings
+# Rustfmt all listings. There are a handful of listings we don't want to
+# rustfmt and skipping doesn't work; those will have a `rustfmt-ignore` file in
+# their directory that explains why.
 echo 'Formatting all listings...'
-find -s listings -name Cargo.toml -print0 | while IFS= read -r -d '' f; do
-    dir_to_fmt=$(dirname "$f")
-
-    # There are a handful of listings we don't want to rustfmt and skipping
-    # doesn't work; those will have a file in their directory that explains why.
-    if [ ! -f "${dir_to_fmt}/rustfmt-ignore" ]; then
-        cd "$dir_to_fmt"
-        cargo fmt --all && true
-        cd - > /dev/null
-    fi
-done
+cargo run --bin format_listings
 
 # Get listings without anchor comments in tmp by compiling a release listings
 # artifact
@@ -86,8 +78,8 @@ done
 echo 'Building book into tmp/book-after after updating...'
 mdbook build -d tmp/book-after
 
-# Run the megadiff script that removes all files that are the same, leaving only files to audit
-echo 'Removing tmp files that had no changes from the update...'
-./tools/megadiff.sh
+# Compare the two builds and write a report of only the pages to audit
+echo 'Generating a report of the pages that changed in the update...'
+cargo run --bin megadiff
 
 echo 'Done.'
\ No newline at end of file